@@ -0,0 +1,138 @@
+// Swift mirrors of the holo-wtf-api models, generated from the Rust types. Do not edit, run
+// `cargo run --bin gen_swift` in holo-wtf-api instead.
+//
+// Dates are RFC 3339, decode them with `JSONDecoder.DateDecodingStrategy.iso8601`. Whether the
+// data is stale is not part of these bodies, read the `X-Calendar-Stale` response header.
+
+import Foundation
+
+/// What changed since a cursor handed out by `/concerts/changes`. Both `added` and `updated`
+/// are meant to be upserted, `removed` lists the IDs to drop. With `full_sync` the cursor could
+/// not be answered exactly and `added` holds every concert, anything else the client kept
+/// should be dropped.
+public struct ConcertChanges: Codable, Hashable {
+    public let added: [LiveConcert]
+    public let cursor: String
+    public let fullSync: Bool
+    public let removed: [UUID]
+    public let updated: [LiveConcert]
+
+    enum CodingKeys: String, CodingKey {
+        case added
+        case cursor
+        case fullSync = "full_sync"
+        case removed
+        case updated
+    }
+}
+
+public struct ConcertPage: Codable, Hashable {
+    public let concerts: [LiveConcert]
+    public let page: Int
+    public let perPage: Int
+    public let total: Int
+
+    enum CodingKeys: String, CodingKey {
+        case concerts
+        case page
+        case perPage = "per_page"
+        case total
+    }
+}
+
+public enum JpyPrice: Codable, Hashable {
+    case tbd
+    case free
+    case fixed(Int)
+    case multiTier(Int)
+
+    enum CodingKeys: String, CodingKey {
+        case tag
+        case content
+    }
+
+    public init(from decoder: Decoder) throws {
+        let container = try decoder.container(keyedBy: CodingKeys.self)
+        switch try container.decode(String.self, forKey: .tag) {
+        case "Tbd": self = .tbd
+        case "Free": self = .free
+        case "Fixed": self = .fixed(try container.decode(Int.self, forKey: .content))
+        case "MultiTier": self = .multiTier(try container.decode(Int.self, forKey: .content))
+        case let unknown: throw DecodingError.dataCorruptedError(forKey: .tag, in: container, debugDescription: "unknown JpyPrice \(unknown)")
+        }
+    }
+
+    public func encode(to encoder: Encoder) throws {
+        var container = encoder.container(keyedBy: CodingKeys.self)
+        switch self {
+        case .tbd: try container.encode("Tbd", forKey: .tag)
+        case .free: try container.encode("Free", forKey: .tag)
+        case .fixed(let content):
+            try container.encode("Fixed", forKey: .tag)
+            try container.encode(content, forKey: .content)
+        case .multiTier(let content):
+            try container.encode("MultiTier", forKey: .tag)
+            try container.encode(content, forKey: .content)
+        }
+    }
+}
+
+public struct LiveConcert: Codable, Hashable {
+    public let description: String
+    public let endTime: Date?
+    public let format: LiveFormat
+    public let id: UUID
+    public let imageUrl: URL?
+    public let isAllDay: Bool
+    public let jpyPrice: JpyPrice
+    public let officialLink: URL?
+    public let platform: Platform
+    public let startTime: Date
+    public let status: LiveStatus
+    public let ticketLink: URL?
+    public let title: String
+    public let twitterUrl: URL?
+    public let uid: String
+    public let youtubeLink: URL?
+
+    enum CodingKeys: String, CodingKey {
+        case description
+        case endTime = "end_time"
+        case format
+        case id
+        case imageUrl = "image_url"
+        case isAllDay = "is_all_day"
+        case jpyPrice = "jpy_price"
+        case officialLink = "official_link"
+        case platform
+        case startTime = "start_time"
+        case status
+        case ticketLink = "ticket_link"
+        case title
+        case twitterUrl = "twitter_url"
+        case uid
+        case youtubeLink = "youtube_link"
+    }
+}
+
+public enum LiveFormat: String, Codable, CaseIterable {
+    case online = "Online"
+    case irl = "Irl"
+    case both = "Both"
+}
+
+public enum LiveStatus: String, Codable, CaseIterable {
+    case upcoming = "Upcoming"
+    case live = "Live"
+    case ended = "Ended"
+}
+
+public enum Platform: String, Codable, CaseIterable {
+    case niconico = "Niconico"
+    case spwn = "Spwn"
+    case tba = "Tba"
+    case youtube = "Youtube"
+    case zan = "Zan"
+    case zaiko = "Zaiko"
+    case other = "Other"
+}
@@ -0,0 +1,22 @@
+//! Writes the Swift mirrors of the models to `schema/Models.swift`, or to the path given as the
+//! only argument, e.g. straight into a checkout of the app.
+
+use holo_wtf_api::swift::get_swift_from_models;
+use std::process::ExitCode;
+
+const DEFAULT_PATH: &str = "schema/Models.swift";
+
+fn main() -> ExitCode {
+    let path = std::env::args().nth(1).unwrap_or_else(|| String::from(DEFAULT_PATH));
+
+    match std::fs::write(&path, get_swift_from_models()) {
+        Ok(()) => {
+            println!("wrote {}", path);
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("writing {} failed, {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::models::{ConcertPage, LiveConcert, LiveStatus};
+use chrono::{DateTime, Datelike, Utc};
+use chrono_tz::Tz;
+
+const DEFAULT_PER_PAGE: usize = 20;
+const MAX_PER_PAGE: usize = 100;
+
+/// Raw query parameters of the past concert archive, validated by [`ArchiveQuery::to_archive_filter`].
+#[derive(Debug, Default, FromForm)]
+pub struct ArchiveQuery<'r> {
+    pub page: Option<&'r str>,
+    pub per_page: Option<&'r str>,
+    pub year: Option<&'r str>,
+    pub month: Option<&'r str>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ArchiveFilter {
+    pub page: usize,
+    pub per_page: usize,
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    pub tz: Tz,
+}
+
+impl ArchiveQuery<'_> {
+    /// Builds the filter, matching `year` and `month` against start times in `tz`.
+    pub fn to_archive_filter(&self, tz: Tz) -> Result<ArchiveFilter, String> {
+        let page = match self.page {
+            Some(p) => get_positive_number_from_param("page", p)?,
+            None => 1
+        };
+        let per_page = match self.per_page {
+            Some(p) => get_positive_number_from_param("per_page", p)?.min(MAX_PER_PAGE),
+            None => DEFAULT_PER_PAGE
+        };
+        let year = self.year
+            .map(|y| y.trim().parse::<i32>().map_err(|_| format!("year must be a number, got \"{}\"", y)))
+            .transpose()?;
+        let month = self.month
+            .map(|m| match m.trim().parse::<u32>() {
+                Ok(m) if (1..=12).contains(&m) => Ok(m),
+                _ => Err(format!("month must be between 1 and 12, got \"{}\"", m))
+            })
+            .transpose()?;
+
+        if month.is_some() && year.is_none() {
+            return Err(String::from("month can only be used together with year"));
+        }
+
+        Ok(ArchiveFilter { page, per_page, year, month, tz })
+    }
+}
+
+impl ArchiveFilter {
+    /// Whether the concert started in the requested year and month.
+    pub fn matches(&self, concert: &LiveConcert) -> bool {
+        let local_start = concert.start_time.with_timezone(&self.tz);
+
+        self.year.is_none_or(|y| local_start.year() == y)
+            && self.month.is_none_or(|m| local_start.month() == m)
+    }
+}
+
+fn get_positive_number_from_param(name: &str, value: &str) -> Result<usize, String> {
+    match value.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} must be a positive number, got \"{}\"", name, value))
+    }
+}
+
+/// Concerts that ended before `now`, newest first, cut down to the requested page.
+pub fn get_past_concerts(concerts: &[LiveConcert], filter: &ArchiveFilter, now: DateTime<Utc>) -> ConcertPage {
+    let mut past: Vec<&LiveConcert> = concerts
+        .iter()
+        .filter(|c| c.status_at(now) == LiveStatus::Ended)
+        .filter(|c| filter.matches(c))
+        .collect();
+    past.sort_by_key(|c| std::cmp::Reverse(c.start_time));
+
+    let total = past.len();
+    let concerts = past
+        .into_iter()
+        .skip((filter.page - 1).saturating_mul(filter.per_page))
+        .take(filter.per_page)
+        .map(|c| c.clone().with_status_at(now))
+        .collect();
+
+    ConcertPage { concerts, page: filter.page, per_page: filter.per_page, total }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        archive::{get_past_concerts, ArchiveQuery},
+        models::{ConcertBuilder, LiveConcert},
+    };
+    use chrono::{DateTime, TimeZone, Utc};
+    use chrono_tz::Asia::Tokyo;
+    
+
+    fn concert(title: &str, start_time: DateTime<Utc>) -> LiveConcert {
+        ConcertBuilder::new().uid(&format!("{}@test", title)).title(title).starts(start_time).build()
+    }
+
+    fn concerts() -> Vec<LiveConcert> {
+        vec![
+            concert("October", Utc.with_ymd_and_hms(2026, 10, 3, 10, 0, 0).unwrap()),
+            concert("Future", Utc.with_ymd_and_hms(2026, 12, 24, 10, 0, 0).unwrap()),
+            // 2026-09-01 01:00 in Japan
+            concert("September", Utc.with_ymd_and_hms(2026, 8, 31, 16, 0, 0).unwrap()),
+            concert("Last year", Utc.with_ymd_and_hms(2025, 10, 3, 10, 0, 0).unwrap()),
+        ]
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_past_concerts_are_newest_first() {
+        let filter = ArchiveQuery::default().to_archive_filter(Tokyo).unwrap();
+        let page = get_past_concerts(&concerts(), &filter, now());
+        let titles: Vec<_> = page.concerts.iter().map(|c| c.title.as_str()).collect();
+
+        assert_eq!(titles, vec!["October", "September", "Last year"]);
+        assert_eq!(page.total, 3);
+    }
+
+    #[test]
+    fn test_past_concerts_pagination() {
+        let query = ArchiveQuery { page: Some("2"), per_page: Some("2"), ..Default::default() };
+        let page = get_past_concerts(&concerts(), &query.to_archive_filter(Tokyo).unwrap(), now());
+
+        assert_eq!(page.concerts.len(), 1);
+        assert_eq!(page.concerts[0].title, "Last year");
+        assert_eq!(page.total, 3);
+    }
+
+    #[test]
+    fn test_past_concerts_by_month_in_japan_time() {
+        let query = ArchiveQuery { year: Some("2026"), month: Some("9"), ..Default::default() };
+        let page = get_past_concerts(&concerts(), &query.to_archive_filter(Tokyo).unwrap(), now());
+
+        assert_eq!(page.concerts.len(), 1);
+        assert_eq!(page.concerts[0].title, "September");
+    }
+
+    #[test]
+    fn test_month_without_year_is_rejected() {
+        let query = ArchiveQuery { month: Some("9"), ..Default::default() };
+        assert!(query.to_archive_filter(Tokyo).is_err());
+    }
+
+    #[test]
+    fn test_zero_page_is_rejected() {
+        let query = ArchiveQuery { page: Some("0"), ..Default::default() };
+        assert!(query.to_archive_filter(Tokyo).is_err());
+    }
+}
@@ -1,99 +1,126 @@
-use super::models::{LiveFormat, JpyPrice, Platform, LiveConcert};
-use chrono::{DateTime, offset, NaiveTime, Utc, TimeZone};
-use chrono_tz::Tz;
+use super::models::{LiveFormat, JpyPrice, Platform, LiveConcert, LiveStatus};
+use super::parse_error::ParseError;
+use super::timezone::TimeZoneResolver;
+use chrono::{DateTime, Duration, offset, Utc};
 use regex::{Regex, RegexSet};
-use icalendar::{Event, Component, DatePerhapsTime, CalendarDateTime};
+use icalendar::{Event, Component, DatePerhapsTime};
 use url::Url;
-use log::{error, info};
+use log::info;
 use uuid::Uuid;
 
-pub async fn get_concert_calendar_in_string() -> Result<String, reqwest::Error> {
-    let res_text = reqwest::get("https://ics.teamup.com/feed/ks58vf85ajmc6pd7vu/0.ics").await?.text().await?;
-    Ok(res_text)
+/// Namespace for the UUIDv5 concert IDs, changing it changes every ID handed out to clients.
+const CONCERT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6a0f_3c1e_5b7d_4e2a_9c84_d1f0_7b3a_52e6);
+
+/// Turns an event into a concert, assuming `default_category` when the event has no CATEGORIES.
+pub fn get_concert_from_event(e: &Event, resolver: &TimeZoneResolver, default_category: Option<&str>) -> Result<LiveConcert, ParseError> {
+    let uid = e.get_uid()
+        .ok_or(ParseError::missing_property("UID"))?.trim();
+    get_concert_from_event_with_uid(e, uid, resolver, default_category)
+        .map_err(|err| err.with_uid(uid))
 }
 
-pub fn get_concert_from_event(e: &Event) -> Result<LiveConcert, String> {
+fn get_concert_from_event_with_uid(e: &Event, uid: &str, resolver: &TimeZoneResolver, default_category: Option<&str>) -> Result<LiveConcert, ParseError> {
+    let id = get_concert_id(uid, e.property_value("RECURRENCE-ID"));
     let summary_str = e.get_summary()
-        .ok_or("failed to get summary")
-        .map_err(|e| {
-            error!("{}", e);
-            e.to_string()
-        })?.trim();
-    let category_str = e.property_value("CATEGORIES")
-        .ok_or("failed to get category")
-        .map_err(|e| {
-            error!("{}", e);
-            e.to_string()
-        })?.trim();
-
-    let (title, jpy_price, format) = get_title_price_and_platform_from_summary(summary_str)
-        .map_err(|e| {
-            error!("{}", e);
-            e
-        })?;
-    let platform = get_platform_from_tag(category_str)
-        .map_err(|e| {
-            error!("{}", e);
-            e
-        })?;
+        .ok_or(ParseError::missing_property("SUMMARY"))?.trim();
+    let category_str = get_category_from_event(e)
+        .or(default_category)
+        .ok_or(ParseError::missing_property("CATEGORIES"))?.trim();
+
+    let (title, jpy_price, format) = get_title_price_and_platform_from_summary(summary_str)?;
+    let platform = get_platform_from_tag(category_str)?;
     let description = e.get_description()
-        .ok_or("failed to get description")
-        .map_err(|e| {
-            error!("{}", e);
-            e.to_string()
-        })?;
+        .ok_or(ParseError::missing_property("DESCRIPTION"))?;
     let trimmed_description = remove_form_link_from_description_and_trim(String::from(description));
-    let start_time = get_start_time_from_event(e)
-        .map_err(|e| {
-            error!("{}", e);
-            e
-        })?;
-    let image_url: Option<Url> = get_image_url_from_event(e).map_err(|_| info!("returning null for image url")).ok();
-    let twitter_url: Option<Url> = get_twitter_url_from_description(trimmed_description.as_str()).map_err(|_| info!("returning null for twitter url")).ok();
-    let youtube_link: Option<Url> = get_youtube_link_from_description(trimmed_description.as_str()).map_err(|_| info!("returning null for youtube url")).ok();
-    let ticket_link: Option<Url> = get_ticket_link_from_description(trimmed_description.as_str()).map_err(|_| info!("returning null for ticket url")).ok();
-    let official_link: Option<Url> = get_official_link_from_description(trimmed_description.as_str()).map_err(|_| info!("returning null for official url")).ok();
-
-    Ok(LiveConcert { id: Uuid::new_v4(), title, format, jpy_price, platform, description: trimmed_description, start_time, image_url, twitter_url, youtube_link, ticket_link, official_link })
+    let start_time = get_start_time_from_event(e, resolver)?;
+    let end_time = get_end_time_from_event(e, start_time, resolver)?;
+    let image_url: Option<Url> = get_image_url_from_event(e).map_err(|e| info!("returning null for image url, {}", e)).ok();
+    let twitter_url: Option<Url> = get_twitter_url_from_description(trimmed_description.as_str()).map_err(|e| info!("returning null for twitter url, {}", e)).ok();
+    let youtube_link: Option<Url> = get_youtube_link_from_description(trimmed_description.as_str()).map_err(|e| info!("returning null for youtube url, {}", e)).ok();
+    let ticket_link: Option<Url> = get_ticket_link_from_description(trimmed_description.as_str()).map_err(|e| info!("returning null for ticket url, {}", e)).ok();
+    let official_link: Option<Url> = get_official_link_from_description(trimmed_description.as_str()).map_err(|e| info!("returning null for official url, {}", e)).ok();
+
+    let concert = LiveConcert { id, uid: String::from(uid), title, format, jpy_price, platform, description: trimmed_description, start_time, end_time, is_all_day: is_all_day_event(e), status: LiveStatus::Upcoming, image_url, twitter_url, youtube_link, ticket_link, official_link };
+    Ok(concert.with_status_at(offset::Utc::now()))
+}
+
+/// Derives the concert ID from the event UID, and the RECURRENCE-ID when the event overrides
+/// a single instance of a recurring series, so the same event keeps its ID across fetches.
+pub fn get_concert_id(uid: &str, recurrence_id: Option<&str>) -> Uuid {
+    let name = match recurrence_id {
+        Some(recurrence_id) => format!("{}/{}", uid, recurrence_id.trim()),
+        None => String::from(uid)
+    };
+
+    Uuid::new_v5(&CONCERT_ID_NAMESPACE, name.as_bytes())
+}
+
+pub fn get_category_from_event(e: &Event) -> Option<&str> {
+    // newer icalendar versions keep CATEGORIES among the multi properties
+    e.property_value("CATEGORIES")
+        .or_else(|| e.multi_properties().get("CATEGORIES")?.first().map(|p| p.value()))
 }
 
-pub fn get_start_time_from_event(event: &Event) -> Result<DateTime<Utc>, String> {
-    let start = event.get_start();
-
-    match start {
-        Some(d) => match d {
-            DatePerhapsTime::Date(naive_date) => {
-                match naive_date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()).and_local_timezone(offset::Utc) {
-                    offset::LocalResult::Single(t) => Ok(t),
-                    _ => Err(String::from("start time unavailable"))
-                }
-            },
-            DatePerhapsTime::DateTime(date_time) => match date_time {
-                CalendarDateTime::Utc(utc) => Ok(utc),
-                CalendarDateTime::Floating(naive) => {
-                    match naive.and_local_timezone(offset::Utc) {
-                        offset::LocalResult::Single(t) => Ok(t),
-                        _ => Err(String::from("start time unavailable"))
-                    }
-                },
-                CalendarDateTime::WithTimezone { date_time, tzid } => {
-                    let tz: Tz = tzid.parse().unwrap();
-                    match date_time.and_local_timezone(tz) {
-                        offset::LocalResult::Single(t) => Ok(Utc.from_utc_datetime(&t.naive_utc())),
-                        _ => Err(String::from("start time unavailable"))
-                    }
-                }
-            }
-        },
-        _ => Err(String::from("start time unavailable"))
+pub fn get_start_time_from_event(event: &Event, resolver: &TimeZoneResolver) -> Result<DateTime<Utc>, ParseError> {
+    match event.get_start() {
+        Some(d) => resolver.resolve(&d),
+        _ => Err(ParseError::missing_property("DTSTART"))
     }
 }
 
-pub fn get_title_price_and_platform_from_summary(summary: &str) -> Result<(String, JpyPrice, LiveFormat), String> {
+/// Reads the end from DTEND, or from DURATION added to the start. An all-day event without
+/// either lasts the whole day, any other event without them gets `None`.
+pub fn get_end_time_from_event(event: &Event, start_time: DateTime<Utc>, resolver: &TimeZoneResolver) -> Result<Option<DateTime<Utc>>, ParseError> {
+    if let Some(d) = event.get_end() {
+        return resolver.resolve(&d).map(Some);
+    }
+
+    match event.property_value("DURATION") {
+        Some(duration) => start_time.checked_add_signed(get_duration_from_string(duration)?)
+            .map(Some)
+            .ok_or(ParseError::bad_time(duration)),
+        None if is_all_day_event(event) => Ok(start_time.checked_add_signed(Duration::days(1))),
+        None => Ok(None)
+    }
+}
+
+pub fn is_all_day_event(event: &Event) -> bool {
+    matches!(event.get_start(), Some(DatePerhapsTime::Date(_)))
+}
+
+/// Parses an RFC 5545 DURATION value such as `PT2H30M` or `P1D`.
+pub fn get_duration_from_string(duration: &str) -> Result<Duration, ParseError> {
+    let matcher = Regex::new(r"^([+-])?P(?:(\d+)W)?(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)S)?)?$").unwrap();
+    let matched = matcher.captures(duration.trim())
+        .filter(|m| (2..=6).any(|i| m.get(i).is_some()))
+        .ok_or(ParseError::bad_time(duration))?;
+
+    let part = |i: usize| matched.get(i).map_or(Ok(0), |m| m.as_str().parse::<i64>())
+        .map_err(|_| ParseError::bad_time(duration));
+    // chrono panics on durations out of its range, so every step is checked
+    let parsed = [
+        Duration::try_weeks(part(2)?),
+        Duration::try_days(part(3)?),
+        Duration::try_hours(part(4)?),
+        Duration::try_minutes(part(5)?),
+        Duration::try_seconds(part(6)?),
+    ]
+        .into_iter()
+        .try_fold(Duration::zero(), |total, d| total.checked_add(&d?))
+        .ok_or(ParseError::bad_time(duration))?;
+
+    if matched.get(1).is_some_and(|sign| sign.as_str() == "-") {
+        Ok(-parsed)
+    } else {
+        Ok(parsed)
+    }
+}
+
+pub fn get_title_price_and_platform_from_summary(summary: &str) -> Result<(String, JpyPrice, LiveFormat), ParseError> {
     // try match "(price)(format)title" first
     let first_match = Regex::new(r"^\((.*)\)\((.*)\)(.+)$").unwrap();
     let matched = first_match.captures(summary)
-        .ok_or(format!("Calendar event summary parsing failed, the text is \"{}\"", summary))?;
+        .ok_or(ParseError::bad_summary(summary))?;
 
     let price_text = &matched[1];
     let price_parsed = get_price_from_string(price_text)?;
@@ -106,7 +133,7 @@ pub fn get_title_price_and_platform_from_summary(summary: &str) -> Result<(Strin
     Ok((title, price_parsed, format_parsed))
 }
 
-pub fn get_price_from_string(price: &str) -> Result<JpyPrice, String> {
+pub fn get_price_from_string(price: &str) -> Result<JpyPrice, ParseError> {
     if price.to_lowercase().contains("tba") || price.to_lowercase().contains("tbd")  {
         return Ok(JpyPrice::Tbd);
     }
@@ -122,8 +149,7 @@ pub fn get_price_from_string(price: &str) -> Result<JpyPrice, String> {
         if let Ok(price) = price_text.parse::<i32>() {
             return Ok(JpyPrice::Fixed(price));
         } else {
-            error!("Price conversion failed, the string is {}", price_text);
-            return Err(String::from("Price conversion failed"));
+            return Err(ParseError::unknown_price(price));
         }
     }
 
@@ -134,15 +160,14 @@ pub fn get_price_from_string(price: &str) -> Result<JpyPrice, String> {
         if let Ok(price) = price_text.parse::<i32>() {
             return Ok(JpyPrice::MultiTier(price));
         } else {
-            error!("Price conversion failed, the string is {}", price_text);
-            return Err(String::from("Price conversion failed"));
+            return Err(ParseError::unknown_price(price));
         }
     }
 
-    return Err(String::from("Price conversion failed"));
+    Err(ParseError::unknown_price(price))
 }
 
-pub fn get_format_from_string(platform: &str) -> Result<LiveFormat, String> {
+pub fn get_format_from_string(platform: &str) -> Result<LiveFormat, ParseError> {
     if platform.contains("🌐🪑") {
         Ok(LiveFormat::Both)
     } else if platform.contains('🌐') {
@@ -150,12 +175,11 @@ pub fn get_format_from_string(platform: &str) -> Result<LiveFormat, String> {
     } else if platform.contains('🪑') {
        Ok(LiveFormat::Irl)
     } else {
-        error!("Live format conversion failed, the text is {}", platform);
-        Err(String::from("Live format conversion failed"))
+        Err(ParseError::unknown_format(platform))
     }
 }
 
-pub fn get_platform_from_tag(tag_string: &str) -> Result<Platform, String> {
+pub fn get_platform_from_tag(tag_string: &str) -> Result<Platform, ParseError> {
     let lowercased = tag_string.to_lowercase();
 
     if lowercased == "spwn" {
@@ -173,59 +197,55 @@ pub fn get_platform_from_tag(tag_string: &str) -> Result<Platform, String> {
     } else if lowercased == "other" {
         Ok(Platform::Other)
     } else {
-        error!("Calendar category parsing failed, the text is \"{}\"", tag_string);
-        Err(String::from("Calendar category parsing failed"))
+        Err(ParseError::unknown_category(tag_string))
     }
 }
 
-pub fn get_image_url_from_description(description: &str) -> Result<Url, String> {
+pub fn get_image_url_from_description(description: &str) -> Result<Url, ParseError> {
     let first_try_match = Regex::new(r"!Image: (https?://(www\.)?[-a-zA-Z0-9@:%._\+~#=]{1,256}\.[a-zA-Z0-9()]{1,6}\b([-a-zA-Z0-9()@%_\+.~#?&//=]*))").unwrap();
     let second_try_match = Regex::new(r"!.*?: (https?://(www\.)?[-a-zA-Z0-9@:%._\+~#=]{1,256}\.[a-zA-Z0-9()]{1,6}\b([-a-zA-Z0-9()@%_\+.~#?&//=]*))").unwrap();
 
     if let Some(matched) = first_try_match.captures(description) {
         let url = &matched[1];
-        let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+        let parsed = Url::parse(url).map_err(|_| ParseError::bad_url(url))?;
         Ok(parsed)
     } else {
         if let Some(second_try) = second_try_match.captures(description) {
             let url = &second_try[1];
-            let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+            let parsed = Url::parse(url).map_err(|_| ParseError::bad_url(url))?;
             return Ok(parsed);
         }
 
-        error!("image url parse failed, the description is \"{}\"", description);
-        Err(String::from("image url parse failed"))
+        Err(ParseError::missing_property("image url"))
     }
 }
 
-pub fn get_twitter_url_from_description(description: &str) -> Result<Url, String> {
+pub fn get_twitter_url_from_description(description: &str) -> Result<Url, ParseError> {
     let matcher = Regex::new(r"(https?://(www\.)?twitter\.com\b([-a-zA-Z0-9()@%_\+.~#?&//=]*))").unwrap();
 
     if let Some(matched) = matcher.captures_iter(description).last() {
         let twitter_url = &matched[1];
-        let parsed = Url::parse(twitter_url).map_err(|e| e.to_string())?;
+        let parsed = Url::parse(twitter_url).map_err(|_| ParseError::bad_url(twitter_url))?;
         Ok(parsed)
     } else {
-        error!("twitter url parse failed, the description is \"{}\"", description);
-        Err(String::from("twitter url parse failed"))
+        Err(ParseError::missing_property("twitter url"))
     }
 }
 
-pub fn get_youtube_link_from_description(description: &str) -> Result<Url, String> {
+pub fn get_youtube_link_from_description(description: &str) -> Result<Url, ParseError> {
     let matcher = Regex::new(r"http(?:s?)://(?:www\.)?youtu(?:be\.com/watch\?v=|\.be/)([\w\-_]*)(&(amp;)?[\w\?=]*)?").unwrap();
 
     if let Some(matched) = matcher.captures(description) {
         let youtube_url = &matched[0];
-        let parsed = Url::parse(youtube_url).map_err(|e| e.to_string())?;
+        let parsed = Url::parse(youtube_url).map_err(|_| ParseError::bad_url(youtube_url))?;
         Ok(parsed)
     } else {
-        error!("youtube url parse failed, the description is \"{}\"", description);
-        Err(String::from("youtube url parse failed"))
+        Err(ParseError::missing_property("youtube url"))
     }
 }
 
-pub fn get_ticket_link_from_description(description: &str) -> Result<Url, String> {
-    let set = RegexSet::new(&[
+pub fn get_ticket_link_from_description(description: &str) -> Result<Url, ParseError> {
+    let set = RegexSet::new([
         r"[T|t]icket (?:[L|l]ink|site):\s?(https?://(?:www\.)?[-a-zA-Z0-9@%._\+~#=]{1,256}\.[a-zA-Z0-9()]{1,6}\b(?:[-a-zA-Z0-9()@%_\+.~#?&//=]*))",
         r"(https?://(www\.)?zan-live\.com\b([-a-zA-Z0-9()@%_\+.~#?&//=]*))",
         r"(https?://virtual\.spwn\.jp\b([-a-zA-Z0-9()@%_\+.~#?&//=]*))",
@@ -240,42 +260,35 @@ pub fn get_ticket_link_from_description(description: &str) -> Result<Url, String
     if let Some(first_idx) = matches.first() {
         if let Some(matched) = regexes[*first_idx].captures(description) {
             let ticket_url = &matched[1];
-            let parsed = Url::parse(ticket_url).map_err(|e| e.to_string())?;
+            let parsed = Url::parse(ticket_url).map_err(|_| ParseError::bad_url(ticket_url))?;
             Ok(parsed)
         } else {
-            error!("ticket url parse failed, the description is \"{}\"", description);
-            Err(String::from("ticket url parse failed"))
+            Err(ParseError::missing_property("ticket url"))
         }
     } else {
-        error!("ticket url parse failed, the description is \"{}\"", description);
-        Err(String::from("ticket url parse failed"))
+        Err(ParseError::missing_property("ticket url"))
     }
 }
 
-pub fn get_official_link_from_description(description: &str) -> Result<Url, String> {
+pub fn get_official_link_from_description(description: &str) -> Result<Url, ParseError> {
     let matcher = Regex::new(r"Official site:\s?(https?://(?:www\.)?[-a-zA-Z0-9@:%._\+~#=]{1,256}\.[a-zA-Z0-9()]{1,6}\b(?:[-a-zA-Z0-9()@%_\+.~#?&//=]*))").unwrap();
 
     if let Some(matched) = matcher.captures(description) {
         let official_link = &matched[1];
-        let parsed = Url::parse(official_link).map_err(|e| e.to_string())?;
+        let parsed = Url::parse(official_link).map_err(|_| ParseError::bad_url(official_link))?;
         Ok(parsed)
     } else {
-        error!("official url parse failed, the description is \"{}\"", description);
-        Err(String::from("official url parse failed"))
+        Err(ParseError::missing_property("official url"))
     }
 }
 
-pub fn get_image_url_from_event(e: &Event) -> Result<Url, String> {
+pub fn get_image_url_from_event(e: &Event) -> Result<Url, ParseError> {
     if let Some(image_url) = e.property_value("ATTACH") {
-        let parsed = Url::parse(image_url).map_err(|e| e.to_string())?;
+        let parsed = Url::parse(image_url).map_err(|_| ParseError::bad_url(image_url))?;
         Ok(parsed)
     } else {
         let description = e.get_description()
-        .ok_or("failed to get description")
-        .map_err(|e| {
-            error!("{}", e);
-            e.to_string()
-        })?;
+            .ok_or(ParseError::missing_property("DESCRIPTION"))?;
         let trimmed_description = remove_form_link_from_description_and_trim(String::from(description));
         get_image_url_from_description(&trimmed_description)
     }
@@ -299,10 +312,18 @@ mod tests {
             get_image_url_from_description,
             get_twitter_url_from_description,
             get_youtube_link_from_description,
-            get_ticket_link_from_description
+            get_ticket_link_from_description,
+            get_concert_id,
+            get_duration_from_string,
+            get_end_time_from_event
         }, 
         models::{JpyPrice, LiveFormat, Platform},
+        parse_error::ParseError,
+        timezone::TimeZoneResolver,
     };
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use chrono_tz::Asia::Tokyo;
+    use icalendar::{Component, Event, EventLike};
     use url::Url;
 
     use super::get_official_link_from_description;
@@ -358,7 +379,7 @@ mod tests {
     #[test]
     fn test_format_error_match() {
         let format_str = "asgaeheaf";
-        assert_eq!(get_format_from_string(format_str), Err(String::from("Live format conversion failed")));
+        assert_eq!(get_format_from_string(format_str), Err(ParseError::unknown_format("asgaeheaf")));
     }
 
     #[test]
@@ -394,7 +415,7 @@ mod tests {
     #[test]
     fn test_platform_three() {
         let platform_str = "Some other";
-        assert_eq!(get_platform_from_tag(platform_str), Err(String::from("Calendar category parsing failed")));
+        assert_eq!(get_platform_from_tag(platform_str), Err(ParseError::unknown_category("Some other")));
     }
 
     #[test]
@@ -430,7 +451,7 @@ mod tests {
     #[test]
     fn test_get_image_url_from_description_four() {
         let description = "there is no image in the description";
-        assert_eq!(get_image_url_from_description(description), Err(String::from("image url parse failed")));
+        assert_eq!(get_image_url_from_description(description), Err(ParseError::missing_property("image url")));
     }
 
     #[test]
@@ -485,7 +506,7 @@ Event Suggestion Submission form: https://forms.gle/tZwY1M19YUgUhn9i6"#;
 Official site: https://hololivesuperexpo2023.hololivepro.com/fes/
 
 Event Suggestion Submission form: https://forms.gle/tZwY1M19YUgUhn9i6"#;
-        assert_eq!(get_twitter_url_from_description(description), Err(String::from("twitter url parse failed")));
+        assert_eq!(get_twitter_url_from_description(description), Err(ParseError::missing_property("twitter url")));
     }
 
     #[test]
@@ -505,7 +526,7 @@ Event Suggestion Submission form: https://forms.gle/tZwY1M19YUgUhn9i6"#;
 https://twitter.com/VALIS_Official/status/1588365423128420353
 
 Event Suggestion Submission form: https://forms.gle/tZwY1M19YUgUhn9i6"#;
-        assert_eq!(get_youtube_link_from_description(description), Err(String::from("youtube url parse failed")));
+        assert_eq!(get_youtube_link_from_description(description), Err(ParseError::missing_property("youtube url")));
     }
 
     #[test]
@@ -590,6 +611,92 @@ Event Suggestion Submission form: https://forms.gle/tZwY1M19YUgUhn9i6"#;
 
 Event Suggestion Submission form: https://forms.gle/tZwY1M19YUgUhn9i6"#;
 
-        assert_eq!(get_official_link_from_description(description), Err(String::from("official url parse failed")));
+        assert_eq!(get_official_link_from_description(description), Err(ParseError::missing_property("official url")));
+    }
+
+    #[test]
+    fn test_concert_id_is_deterministic() {
+        let uid = "TU1234567@teamup.com";
+        assert_eq!(get_concert_id(uid, None), get_concert_id(uid, None));
+    }
+
+    #[test]
+    fn test_concert_id_differs_per_recurrence() {
+        let uid = "TU1234567@teamup.com";
+        let series = get_concert_id(uid, None);
+        let first = get_concert_id(uid, Some("20301101T100000Z"));
+        let second = get_concert_id(uid, Some("20301108T100000Z"));
+
+        assert_ne!(series, first);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_duration_hours_and_minutes() {
+        assert_eq!(get_duration_from_string("PT2H30M"), Ok(Duration::minutes(150)));
+    }
+
+    #[test]
+    fn test_duration_days_and_weeks() {
+        assert_eq!(get_duration_from_string("P1W2D"), Ok(Duration::days(9)));
+    }
+
+    #[test]
+    fn test_duration_negative() {
+        assert_eq!(get_duration_from_string("-PT15M"), Ok(Duration::minutes(-15)));
+    }
+
+    #[test]
+    fn test_duration_out_of_range_is_rejected() {
+        assert_eq!(get_duration_from_string("P99999999999999W"), Err(ParseError::bad_time("P99999999999999W")));
+        assert_eq!(get_duration_from_string("PT99999999999999999S"), Err(ParseError::bad_time("PT99999999999999999S")));
+    }
+
+    #[test]
+    fn test_duration_empty_is_rejected() {
+        assert!(get_duration_from_string("PT").is_err());
+        assert!(get_duration_from_string("two hours").is_err());
+    }
+
+    #[test]
+    fn test_end_time_from_dtend() {
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 10, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+        let event = Event::new().starts(start).ends(end).done();
+
+        assert_eq!(get_end_time_from_event(&event, start, &TimeZoneResolver::new(Tokyo)), Ok(Some(end)));
+    }
+
+    #[test]
+    fn test_end_time_from_duration() {
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 10, 0, 0).unwrap();
+        let event = Event::new().starts(start).add_property("DURATION", "PT1H30M").done();
+
+        assert_eq!(get_end_time_from_event(&event, start, &TimeZoneResolver::new(Tokyo)), Ok(Some(start + Duration::minutes(90))));
+    }
+
+    #[test]
+    fn test_end_time_past_the_last_date_is_rejected() {
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 10, 0, 0).unwrap();
+        // in range as a duration, but past the last date chrono can hold
+        let event = Event::new().starts(start).add_property("DURATION", "P100000000D").done();
+
+        assert_eq!(get_end_time_from_event(&event, start, &TimeZoneResolver::new(Tokyo)), Err(ParseError::bad_time("P100000000D")));
+    }
+
+    #[test]
+    fn test_end_time_missing() {
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 10, 0, 0).unwrap();
+        let event = Event::new().starts(start).done();
+
+        assert_eq!(get_end_time_from_event(&event, start, &TimeZoneResolver::new(Tokyo)), Ok(None));
+    }
+
+    #[test]
+    fn test_end_time_of_all_day_event() {
+        let event = Event::new().starts(NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()).done();
+        let start = Utc.with_ymd_and_hms(2029, 12, 31, 15, 0, 0).unwrap();
+
+        assert_eq!(get_end_time_from_event(&event, start, &TimeZoneResolver::new(Tokyo)), Ok(Some(start + Duration::days(1))));
     }
 }
@@ -0,0 +1,277 @@
+use super::models::{ConcertChanges, LiveConcert};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// How long a removed concert is remembered, older cursors get a full sync instead.
+const TOMBSTONE_DAYS: i64 = 30;
+
+#[derive(Debug, Clone)]
+struct ChangeEntry {
+    hash: u64,
+    /// Revision the concert (re)appeared in.
+    added: u64,
+    /// Revision its content last changed in.
+    changed: u64,
+    added_at: DateTime<Utc>,
+    changed_at: DateTime<Utc>,
+    removed: Option<(u64, DateTime<Utc>)>,
+}
+
+/// Revision history of the concert list, built from one content hash per concert ID (and so
+/// per event UID) and kept across refreshes.
+///
+/// Cursors look like `<epoch>-<revision>`. The epoch is when this process started keeping
+/// history, so a cursor from before a restart is recognised and answered with a full sync.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeLog {
+    epoch: i64,
+    revision: u64,
+    /// Oldest revision that can still be answered exactly, raised as tombstones are dropped.
+    horizon: u64,
+    /// When the list last moved to a new revision, `None` before the first update.
+    revised_at: Option<DateTime<Utc>>,
+    entries: HashMap<Uuid, ChangeEntry>,
+}
+
+impl ChangeLog {
+    pub fn new(epoch: DateTime<Utc>) -> Self {
+        Self { epoch: epoch.timestamp_millis(), ..Default::default() }
+    }
+
+    pub fn cursor(&self) -> String {
+        format!("{}-{}", self.epoch, self.revision)
+    }
+
+    pub fn revised_at(&self) -> Option<DateTime<Utc>> {
+        self.revised_at
+    }
+
+    /// Compares `concerts` against the previous list, moving to a new revision when anything
+    /// was added, changed or removed.
+    pub fn update(&mut self, concerts: &[LiveConcert], now: DateTime<Utc>) {
+        let next = self.revision + 1;
+        let mut changed = false;
+        let mut present = HashSet::with_capacity(concerts.len());
+
+        for concert in concerts {
+            let hash = concert.content_hash();
+            present.insert(concert.id);
+
+            match self.entries.get_mut(&concert.id) {
+                Some(entry) if entry.removed.is_some() => {
+                    *entry = ChangeEntry { hash, added: next, changed: next, added_at: now, changed_at: now, removed: None };
+                    changed = true;
+                },
+                Some(entry) if entry.hash != hash => {
+                    entry.hash = hash;
+                    entry.changed = next;
+                    entry.changed_at = now;
+                    changed = true;
+                },
+                Some(_) => {},
+                None => {
+                    self.entries.insert(concert.id, ChangeEntry { hash, added: next, changed: next, added_at: now, changed_at: now, removed: None });
+                    changed = true;
+                }
+            }
+        }
+
+        for (id, entry) in self.entries.iter_mut() {
+            if entry.removed.is_none() && !present.contains(id) {
+                entry.removed = Some((next, now));
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.revision = next;
+            self.revised_at = Some(now);
+        }
+
+        let cutoff = now - Duration::days(TOMBSTONE_DAYS);
+        let mut horizon = self.horizon;
+        self.entries.retain(|_, entry| match entry.removed {
+            Some((revision, removed_at)) if removed_at < cutoff => {
+                horizon = horizon.max(revision);
+                false
+            },
+            _ => true
+        });
+        self.horizon = horizon;
+    }
+
+    /// When the concert first showed up and when its content last changed, as far as this
+    /// process has seen.
+    pub fn get_dates(&self, id: &Uuid) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        self.entries
+            .get(id)
+            .filter(|entry| entry.removed.is_none())
+            .map(|entry| (entry.added_at, entry.changed_at))
+    }
+
+    /// What happened to `concerts`, the list the log was last updated with, since `since`.
+    pub fn changes_since(&self, since: Option<&str>, concerts: &[LiveConcert]) -> Result<ConcertChanges, String> {
+        let since = match since {
+            Some(cursor) => self.get_revision_from_cursor(cursor)?,
+            None => None
+        };
+
+        let Some(since) = since else {
+            return Ok(ConcertChanges {
+                cursor: self.cursor(),
+                full_sync: true,
+                added: concerts.to_vec(),
+                updated: vec![],
+                removed: vec![]
+            });
+        };
+
+        let mut added = vec![];
+        let mut updated = vec![];
+        for concert in concerts {
+            match self.entries.get(&concert.id) {
+                Some(entry) if entry.added > since => added.push(concert.clone()),
+                Some(entry) if entry.changed > since => updated.push(concert.clone()),
+                _ => {}
+            }
+        }
+
+        let mut removed: Vec<Uuid> = self.entries
+            .iter()
+            .filter(|(_, entry)| entry.removed.is_some_and(|(revision, _)| revision > since) && entry.added <= since)
+            .map(|(id, _)| *id)
+            .collect();
+        removed.sort();
+
+        Ok(ConcertChanges { cursor: self.cursor(), full_sync: false, added, updated, removed })
+    }
+
+    /// The revision a cursor points at, or `None` when it can only be answered with a full sync.
+    fn get_revision_from_cursor(&self, cursor: &str) -> Result<Option<u64>, String> {
+        let invalid = || format!("since must be a cursor from an earlier response, got \"{}\"", cursor);
+        let (epoch, revision) = cursor.trim().split_once('-').ok_or_else(invalid)?;
+        let epoch = epoch.parse::<i64>().map_err(|_| invalid())?;
+        let revision = revision.parse::<u64>().map_err(|_| invalid())?;
+
+        if epoch != self.epoch || revision < self.horizon {
+            Ok(None)
+        } else if revision > self.revision {
+            Err(invalid())
+        } else {
+            Ok(Some(revision))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        changes::ChangeLog,
+        models::{ConcertBuilder, LiveConcert, LiveStatus},
+    };
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use uuid::Uuid;
+
+    fn concert(id: u128, title: &str) -> LiveConcert {
+        ConcertBuilder::new().id(id).uid(&format!("{}@test", id)).title(title).build()
+    }
+
+    fn epoch() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn titles(concerts: &[LiveConcert]) -> Vec<&str> {
+        concerts.iter().map(|c| c.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_no_cursor_is_a_full_sync() {
+        let mut log = ChangeLog::new(epoch());
+        let concerts = vec![concert(1, "First"), concert(2, "Second")];
+        log.update(&concerts, epoch());
+
+        let changes = log.changes_since(None, &concerts).unwrap();
+        assert!(changes.full_sync);
+        assert_eq!(titles(&changes.added), vec!["First", "Second"]);
+        assert_eq!(changes.cursor, format!("{}-1", epoch().timestamp_millis()));
+    }
+
+    #[test]
+    fn test_added_updated_and_removed() {
+        let mut log = ChangeLog::new(epoch());
+        log.update(&[concert(1, "First"), concert(2, "Second")], epoch());
+        let cursor = log.cursor();
+
+        let concerts = vec![concert(1, "First, now with a new title"), concert(3, "Third")];
+        log.update(&concerts, epoch());
+
+        let changes = log.changes_since(Some(&cursor), &concerts).unwrap();
+        assert!(!changes.full_sync);
+        assert_eq!(titles(&changes.added), vec!["Third"]);
+        assert_eq!(titles(&changes.updated), vec!["First, now with a new title"]);
+        assert_eq!(changes.removed, vec![Uuid::from_u128(2)]);
+    }
+
+    #[test]
+    fn test_status_alone_is_not_a_change() {
+        let mut log = ChangeLog::new(epoch());
+        log.update(&[concert(1, "First")], epoch());
+        let cursor = log.cursor();
+
+        let live = LiveConcert { status: LiveStatus::Live, ..concert(1, "First") };
+        log.update(std::slice::from_ref(&live), epoch() + Duration::hours(1));
+
+        assert_eq!(log.cursor(), cursor);
+        assert_eq!(log.revised_at(), Some(epoch()));
+        let changes = log.changes_since(Some(&cursor), &[live]).unwrap();
+        assert!(changes.added.is_empty() && changes.updated.is_empty() && changes.removed.is_empty());
+    }
+
+    #[test]
+    fn test_concert_added_and_removed_in_between_is_not_reported() {
+        let mut log = ChangeLog::new(epoch());
+        log.update(&[concert(1, "First")], epoch());
+        let cursor = log.cursor();
+
+        log.update(&[concert(1, "First"), concert(2, "Short lived")], epoch());
+        log.update(&[concert(1, "First")], epoch());
+
+        let changes = log.changes_since(Some(&cursor), &[concert(1, "First")]).unwrap();
+        assert!(changes.removed.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_of_another_run_or_past_tombstones_is_a_full_sync() {
+        let mut earlier_run = ChangeLog::new(epoch());
+        earlier_run.update(&[concert(1, "First")], epoch());
+
+        let mut log = ChangeLog::new(epoch() + Duration::hours(1));
+        log.update(&[concert(1, "First")], epoch());
+        assert!(log.changes_since(Some(&earlier_run.cursor()), &[concert(1, "First")]).unwrap().full_sync);
+
+        let cursor = log.cursor();
+        log.update(&[], epoch());
+        log.update(&[concert(3, "Third")], epoch() + Duration::days(31));
+        assert!(log.changes_since(Some(&cursor), &[concert(3, "Third")]).unwrap().full_sync);
+    }
+
+    #[test]
+    fn test_bad_cursors_are_rejected() {
+        let mut log = ChangeLog::new(epoch());
+        log.update(&[concert(1, "First")], epoch());
+
+        assert!(log.changes_since(Some("yesterday"), &[]).is_err());
+        assert!(log.changes_since(Some(&format!("{}-99", epoch().timestamp_millis())), &[]).is_err());
+    }
+
+    #[test]
+    fn test_dates_of_concerts() {
+        let mut log = ChangeLog::new(epoch());
+        log.update(&[concert(1, "First")], epoch());
+        log.update(&[concert(1, "First, renamed")], epoch() + Duration::hours(1));
+
+        assert_eq!(log.get_dates(&Uuid::from_u128(1)), Some((epoch(), epoch() + Duration::hours(1))));
+        assert_eq!(log.get_dates(&Uuid::from_u128(2)), None);
+    }
+}
@@ -0,0 +1,220 @@
+use super::models::{LiveConcert, LiveFormat, Platform};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use icalendar::{Alarm, Calendar, Component, Event, EventLike, Trigger};
+
+pub const EXPORT_CALENDAR_NAME: &str = "holo.wtf concerts";
+
+/// Reminders further ahead than four weeks are almost certainly a typo.
+const MAX_REMINDER_MINUTES: u32 = 4 * 7 * 24 * 60;
+const MAX_REMINDERS: usize = 5;
+
+/// Minutes before the start to remind at, from a list like `60,1440`.
+pub fn get_reminders_from_param(remind: &str) -> Result<Vec<u32>, String> {
+    let mut reminders = vec![];
+    for minutes in remind.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+        match minutes.parse::<u32>() {
+            Ok(m) if (1..=MAX_REMINDER_MINUTES).contains(&m) => reminders.push(m),
+            _ => return Err(format!("remind must list minutes between 1 and {}, got \"{}\"", MAX_REMINDER_MINUTES, minutes))
+        }
+    }
+
+    reminders.sort_unstable();
+    reminders.dedup();
+    if reminders.len() > MAX_REMINDERS {
+        return Err(format!("remind takes at most {} reminders, got {}", MAX_REMINDERS, reminders.len()));
+    }
+    Ok(reminders)
+}
+
+/// Category tag of the platform as the upstream calendar writes it, the inverse of
+/// [`get_platform_from_tag`](super::calendar_parser::get_platform_from_tag).
+pub fn get_tag_from_platform(platform: &Platform) -> &'static str {
+    match platform {
+        Platform::Niconico => "Nico Nico Douga",
+        Platform::Spwn => "SPWN",
+        Platform::Tba => "TBA",
+        Platform::Youtube => "YouTube",
+        Platform::Zan => "Z-aN",
+        Platform::Zaiko => "ZAIKO",
+        Platform::Other => "Other",
+    }
+}
+
+pub fn get_categories_from_format(format: &LiveFormat) -> &'static [&'static str] {
+    match format {
+        LiveFormat::Online => &["Online"],
+        LiveFormat::Irl => &["IRL"],
+        LiveFormat::Both => &["Online", "IRL"],
+    }
+}
+
+/// The link a calendar app should open for the concert, the most official one there is.
+pub fn get_main_link_from_concert(concert: &LiveConcert) -> Option<&url::Url> {
+    concert.official_link.as_ref()
+        .or(concert.ticket_link.as_ref())
+        .or(concert.youtube_link.as_ref())
+        .or(concert.twitter_url.as_ref())
+}
+
+/// A clean event for the concert with an alarm for each of `reminders`, given in minutes before
+/// the start. All-day concerts keep their dates in `tz`, `stamp` becomes DTSTAMP so the same
+/// snapshot always exports to the same text. A concert without an end time gets no DTEND, the
+/// length we assume for its status is not something the calendar said.
+pub fn get_event_from_concert(concert: &LiveConcert, tz: Tz, stamp: DateTime<Utc>, reminders: &[u32]) -> Event {
+    let mut event = Event::with_uid(&format!("{}@holo.wtf", concert.id));
+    event
+        .timestamp(stamp)
+        .summary(&concert.title)
+        .description(&concert.description);
+
+    if concert.is_all_day {
+        let start = concert.start_time.with_timezone(&tz).date_naive();
+        let end = concert.end_time
+            .map(|end| end.with_timezone(&tz).date_naive())
+            .filter(|end| *end > start)
+            .or(start.succ_opt())
+            .unwrap_or(start);
+        event.starts(start).ends(end);
+    } else {
+        event.starts(concert.start_time);
+        if let Some(end) = concert.end_time {
+            event.ends(end);
+        }
+    }
+
+    if let Some(link) = get_main_link_from_concert(concert) {
+        event.url(link.as_str());
+    }
+
+    // one CATEGORIES line each, a comma inside a value would be escaped, and the platform first
+    // since that is where our own parser looks
+    event.add_multi_property("CATEGORIES", get_tag_from_platform(&concert.platform));
+    for category in get_categories_from_format(&concert.format) {
+        event.add_multi_property("CATEGORIES", category);
+    }
+
+    for minutes in reminders {
+        let trigger = Trigger::before_start(Duration::minutes(i64::from(*minutes)));
+        event.alarm(Alarm::display(&concert.title, trigger));
+    }
+
+    event.done()
+}
+
+pub fn get_calendar_from_concerts(concerts: &[LiveConcert], tz: Tz, stamp: DateTime<Utc>, reminders: &[u32]) -> Calendar {
+    let mut calendar = Calendar::new();
+    calendar.name(EXPORT_CALENDAR_NAME);
+    for concert in concerts {
+        calendar.push(get_event_from_concert(concert, tz, stamp, reminders));
+    }
+    calendar.done()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        calendar_parser::{get_category_from_event, get_end_time_from_event, get_platform_from_tag},
+        timezone::TimeZoneResolver,
+        export::{get_calendar_from_concerts, get_reminders_from_param, get_tag_from_platform},
+        models::{ConcertBuilder, JpyPrice, LiveConcert, LiveFormat, Platform},
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use chrono_tz::Asia::Tokyo;
+    use icalendar::{
+        parser::{read_calendar, unfold},
+        Calendar
+    };
+    
+    
+
+    fn concert() -> LiveConcert {
+        ConcertBuilder::new()
+            .uid("first@test")
+            .title("First Live")
+            .format(LiveFormat::Both)
+            .price(JpyPrice::MultiTier(3500))
+            .platform(Platform::Spwn)
+            .description("SPWN link: https://virtual.spwn.jp/events/first")
+            .twitter("https://twitter.com/first")
+            .official("https://example.com/first")
+            .build()
+    }
+
+    fn export(concerts: &[LiveConcert]) -> String {
+        get_calendar_from_concerts(concerts, Tokyo, Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap(), &[]).to_string()
+    }
+
+    #[test]
+    fn test_platform_tags_round_trip() {
+        for platform in [Platform::Niconico, Platform::Spwn, Platform::Tba, Platform::Youtube, Platform::Zan, Platform::Zaiko, Platform::Other] {
+            assert_eq!(get_platform_from_tag(get_tag_from_platform(&platform)), Ok(platform));
+        }
+    }
+
+    #[test]
+    fn test_exported_event() {
+        let ics = export(&[concert()]);
+
+        assert!(ics.contains("UID:00000000-0000-0000-0000-000000000001@holo.wtf\r\n"));
+        assert!(ics.contains("SUMMARY:First Live\r\n"));
+        assert!(ics.contains("DTSTART:20300101T100000Z\r\n"));
+        assert!(!ics.contains("DTEND"));
+        assert!(ics.contains("DTSTAMP:20290101T000000Z\r\n"));
+        assert!(ics.contains("URL:https://example.com/first\r\n"));
+        assert!(ics.contains("CATEGORIES:SPWN\r\nCATEGORIES:Online\r\nCATEGORIES:IRL\r\n"));
+        assert!(ics.contains("X-WR-CALNAME:holo.wtf concerts\r\n"));
+    }
+
+    #[test]
+    fn test_end_time_is_exported_when_known() {
+        let end_time = Utc.with_ymd_and_hms(2030, 1, 1, 12, 30, 0).unwrap();
+        let ics = export(&[LiveConcert { end_time: Some(end_time), ..concert() }]);
+
+        assert!(ics.contains("DTEND:20300101T123000Z\r\n"));
+    }
+
+    #[test]
+    fn test_all_day_event_keeps_japan_dates() {
+        // 2030-01-01 in Japan
+        let start_time = Utc.with_ymd_and_hms(2029, 12, 31, 15, 0, 0).unwrap();
+        let all_day = LiveConcert { is_all_day: true, start_time, end_time: Some(start_time + Duration::days(1)), ..concert() };
+        let ics = export(&[all_day]);
+
+        assert!(ics.contains("DTSTART;VALUE=DATE:20300101\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20300102\r\n"));
+    }
+
+    #[test]
+    fn test_export_parses_back() {
+        let ics = export(&[concert()]);
+        let calendar: Calendar = read_calendar(&unfold(&ics)).unwrap().into();
+        let event = calendar.components.iter().find_map(|c| c.as_event()).unwrap();
+
+        assert_eq!(get_category_from_event(event), Some("SPWN"));
+        assert_eq!(get_end_time_from_event(event, concert().start_time, &TimeZoneResolver::new(Tokyo)), Ok(None));
+    }
+
+    #[test]
+    fn test_export_is_stable() {
+        assert_eq!(export(&[concert()]), export(&[concert()]));
+    }
+
+    #[test]
+    fn test_reminders_param() {
+        assert_eq!(get_reminders_from_param("1440, 60,60"), Ok(vec![60, 1440]));
+        assert!(get_reminders_from_param("0").is_err());
+        assert!(get_reminders_from_param("an hour").is_err());
+        assert!(get_reminders_from_param("1,2,3,4,5,6").is_err());
+    }
+
+    #[test]
+    fn test_reminders_become_alarms() {
+        let ics = get_calendar_from_concerts(&[concert()], Tokyo, Utc::now(), &[60, 1440]).to_string();
+
+        assert_eq!(ics.matches("BEGIN:VALARM\r\n").count(), 2);
+        assert!(ics.contains("ACTION:DISPLAY\r\n"));
+        assert!(ics.contains("TRIGGER;RELATED=START:-PT3600S\r\n"));
+        assert!(ics.contains("TRIGGER;RELATED=START:-PT86400S\r\n"));
+    }
+}
@@ -0,0 +1,33 @@
+use rocket::serde::Deserialize;
+use std::path::PathBuf;
+use url::Url;
+
+const TEAMUP_FEED_URL: &str = "https://ics.teamup.com/feed/ks58vf85ajmc6pd7vu/0.ics";
+
+/// One upstream iCalendar feed, as listed under `feeds` in the Rocket configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Feed {
+    pub name: String,
+    #[serde(flatten)]
+    pub location: FeedLocation,
+    /// Category tag as the feed would write it, e.g. `SPWN`, assumed for events without CATEGORIES.
+    #[serde(default)]
+    pub default_platform: Option<String>,
+}
+
+/// Where a feed is read from, a `url` to poll or a local `path`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum FeedLocation {
+    Url { url: Url },
+    Path { path: PathBuf },
+}
+
+pub fn default_feeds() -> Vec<Feed> {
+    vec![Feed {
+        name: String::from("teamup"),
+        location: FeedLocation::Url { url: Url::parse(TEAMUP_FEED_URL).unwrap() },
+        default_platform: None
+    }]
+}
@@ -0,0 +1,293 @@
+use super::models::{JpyPrice, LiveConcert, LiveFormat, Platform};
+use super::timezone::resolve_local;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+
+/// Raw query parameters accepted by the concert list, validated by [`ConcertQuery::to_filter`].
+///
+/// Every field is taken as text so that malformed values are reported as a 400 by us
+/// instead of a 422 from Rocket's form parser.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "server", derive(FromForm))]
+pub struct ConcertQuery<'r> {
+    pub platform: Option<&'r str>,
+    pub format: Option<&'r str>,
+    pub max_price: Option<&'r str>,
+    pub free: Option<&'r str>,
+    pub from: Option<&'r str>,
+    pub to: Option<&'r str>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ConcertFilter {
+    pub platforms: Option<Vec<Platform>>,
+    pub formats: Option<Vec<LiveFormat>>,
+    pub max_price: Option<i32>,
+    pub free: Option<bool>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ConcertQuery<'_> {
+    /// Builds the filter, reading the `from` and `to` dates in `tz`.
+    pub fn to_filter(&self, tz: Tz) -> Result<ConcertFilter, String> {
+        let platforms = self.platform.map(|p| parse_list(p, get_platform_from_param)).transpose()?;
+        let formats = self.format.map(|f| parse_list(f, get_format_from_param)).transpose()?;
+        let max_price = self.max_price.map(get_max_price_from_param).transpose()?;
+        let free = self.free.map(get_bool_from_param).transpose()?;
+        let from = self.from.map(get_date_from_param).transpose()?;
+        let to = self.to.map(get_date_from_param).transpose()?;
+
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Err(format!("from ({}) is after to ({})", from, to));
+            }
+        }
+
+        Ok(ConcertFilter {
+            platforms,
+            formats,
+            max_price,
+            free,
+            from: from.map(|d| resolve_local(d.and_time(NaiveTime::MIN), tz)),
+            to: to.and_then(|d| d.succ_opt()).map(|d| resolve_local(d.and_time(NaiveTime::MIN), tz)),
+        })
+    }
+}
+
+impl ConcertFilter {
+    /// Whether the concert passes every filter that was set.
+    ///
+    /// An `Online` or `Irl` format also matches concerts held in `Both` formats, `max_price`
+    /// compares against the cheapest tier and never matches a TBD price, and the date range is
+    /// inclusive on both ends.
+    pub fn matches(&self, concert: &LiveConcert) -> bool {
+        if let Some(platforms) = &self.platforms {
+            if !platforms.contains(&concert.platform) {
+                return false;
+            }
+        }
+
+        if let Some(formats) = &self.formats {
+            if !formats.iter().any(|f| f == &concert.format || (f != &LiveFormat::Both && concert.format == LiveFormat::Both)) {
+                return false;
+            }
+        }
+
+        if let Some(max_price) = self.max_price {
+            let within_budget = match concert.jpy_price {
+                JpyPrice::Tbd => false,
+                JpyPrice::Free => true,
+                JpyPrice::Fixed(price) | JpyPrice::MultiTier(price) => price <= max_price,
+            };
+            if !within_budget {
+                return false;
+            }
+        }
+
+        if let Some(free) = self.free {
+            if (concert.jpy_price == JpyPrice::Free) != free {
+                return false;
+            }
+        }
+
+        if let Some(from) = self.from {
+            if concert.start_time < from {
+                return false;
+            }
+        }
+
+        if let Some(to) = self.to {
+            if concert.start_time >= to {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn parse_list<T>(text: &str, parse: fn(&str) -> Result<T, String>) -> Result<Vec<T>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(parse)
+        .collect()
+}
+
+pub fn get_platform_from_param(platform: &str) -> Result<Platform, String> {
+    match platform.to_lowercase().as_str() {
+        "niconico" => Ok(Platform::Niconico),
+        "spwn" => Ok(Platform::Spwn),
+        "tba" => Ok(Platform::Tba),
+        "youtube" => Ok(Platform::Youtube),
+        "zan" | "z-an" => Ok(Platform::Zan),
+        "zaiko" => Ok(Platform::Zaiko),
+        "other" => Ok(Platform::Other),
+        _ => Err(format!("unknown platform \"{}\"", platform))
+    }
+}
+
+/// How a platform is written in a query, the inverse of [`get_platform_from_param`].
+pub fn get_param_from_platform(platform: &Platform) -> &'static str {
+    match platform {
+        Platform::Niconico => "niconico",
+        Platform::Spwn => "spwn",
+        Platform::Tba => "tba",
+        Platform::Youtube => "youtube",
+        Platform::Zan => "zan",
+        Platform::Zaiko => "zaiko",
+        Platform::Other => "other",
+    }
+}
+
+pub fn get_format_from_param(format: &str) -> Result<LiveFormat, String> {
+    match format.to_lowercase().as_str() {
+        "online" => Ok(LiveFormat::Online),
+        "irl" => Ok(LiveFormat::Irl),
+        "both" => Ok(LiveFormat::Both),
+        _ => Err(format!("unknown format \"{}\"", format))
+    }
+}
+
+/// How a format is written in a query, the inverse of [`get_format_from_param`].
+pub fn get_param_from_format(format: &LiveFormat) -> &'static str {
+    match format {
+        LiveFormat::Online => "online",
+        LiveFormat::Irl => "irl",
+        LiveFormat::Both => "both",
+    }
+}
+
+pub fn get_max_price_from_param(price: &str) -> Result<i32, String> {
+    match price.trim().parse::<i32>() {
+        Ok(p) if p >= 0 => Ok(p),
+        _ => Err(format!("max_price must be a non-negative number of yen, got \"{}\"", price))
+    }
+}
+
+pub fn get_bool_from_param(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!("expected true or false, got \"{}\"", value))
+    }
+}
+
+pub fn get_date_from_param(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("dates must look like 2026-11-01, got \"{}\"", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        filter::{
+            get_format_from_param,
+            get_param_from_format,
+            get_param_from_platform,
+            get_platform_from_param,
+            ConcertFilter,
+            ConcertQuery
+        },
+        models::{ConcertBuilder, JpyPrice, LiveConcert, LiveFormat, Platform},
+    };
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Asia::Tokyo;
+    
+
+    fn concert(platform: Platform, format: LiveFormat, jpy_price: JpyPrice) -> LiveConcert {
+        ConcertBuilder::new()
+            .platform(platform)
+            .format(format)
+            .price(jpy_price)
+            .starts(Utc.with_ymd_and_hms(2026, 11, 30, 15, 30, 0).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn test_empty_query_matches_everything() {
+        let filter = ConcertQuery::default().to_filter(Tokyo).unwrap();
+        assert_eq!(filter, ConcertFilter::default());
+        assert!(filter.matches(&concert(Platform::Tba, LiveFormat::Irl, JpyPrice::Tbd)));
+    }
+
+    #[test]
+    fn test_platform_list() {
+        let query = ConcertQuery { platform: Some("spwn, zan"), ..Default::default() };
+        let filter = query.to_filter(Tokyo).unwrap();
+
+        assert_eq!(filter.platforms, Some(vec![Platform::Spwn, Platform::Zan]));
+        assert!(filter.matches(&concert(Platform::Zan, LiveFormat::Online, JpyPrice::Free)));
+        assert!(!filter.matches(&concert(Platform::Zaiko, LiveFormat::Online, JpyPrice::Free)));
+    }
+
+    #[test]
+    fn test_unknown_platform_is_rejected() {
+        let query = ConcertQuery { platform: Some("spwn,twitch"), ..Default::default() };
+        assert_eq!(query.to_filter(Tokyo), Err(String::from("unknown platform \"twitch\"")));
+    }
+
+    #[test]
+    fn test_online_format_includes_both() {
+        let query = ConcertQuery { format: Some("Online"), ..Default::default() };
+        let filter = query.to_filter(Tokyo).unwrap();
+
+        assert!(filter.matches(&concert(Platform::Spwn, LiveFormat::Both, JpyPrice::Free)));
+        assert!(!filter.matches(&concert(Platform::Spwn, LiveFormat::Irl, JpyPrice::Free)));
+    }
+
+    #[test]
+    fn test_max_price() {
+        let query = ConcertQuery { max_price: Some("5000"), ..Default::default() };
+        let filter = query.to_filter(Tokyo).unwrap();
+
+        assert!(filter.matches(&concert(Platform::Spwn, LiveFormat::Online, JpyPrice::MultiTier(5000))));
+        assert!(filter.matches(&concert(Platform::Spwn, LiveFormat::Online, JpyPrice::Free)));
+        assert!(!filter.matches(&concert(Platform::Spwn, LiveFormat::Online, JpyPrice::Fixed(5500))));
+        assert!(!filter.matches(&concert(Platform::Spwn, LiveFormat::Online, JpyPrice::Tbd)));
+    }
+
+    #[test]
+    fn test_bad_max_price_is_rejected() {
+        let query = ConcertQuery { max_price: Some("-1"), ..Default::default() };
+        assert!(query.to_filter(Tokyo).is_err());
+    }
+
+    #[test]
+    fn test_free_false() {
+        let query = ConcertQuery { free: Some("false"), ..Default::default() };
+        let filter = query.to_filter(Tokyo).unwrap();
+
+        assert!(!filter.matches(&concert(Platform::Spwn, LiveFormat::Online, JpyPrice::Free)));
+        assert!(filter.matches(&concert(Platform::Spwn, LiveFormat::Online, JpyPrice::Fixed(1000))));
+    }
+
+    #[test]
+    fn test_date_range_is_inclusive_in_japan_time() {
+        // the test concert starts 2026-12-01 00:30 in Japan
+        let live = concert(Platform::Spwn, LiveFormat::Online, JpyPrice::Free);
+
+        let december = ConcertQuery { from: Some("2026-12-01"), to: Some("2026-12-31"), ..Default::default() };
+        assert!(december.to_filter(Tokyo).unwrap().matches(&live));
+
+        let november = ConcertQuery { from: Some("2026-11-01"), to: Some("2026-11-30"), ..Default::default() };
+        assert!(!november.to_filter(Tokyo).unwrap().matches(&live));
+    }
+
+    #[test]
+    fn test_reversed_date_range_is_rejected() {
+        let query = ConcertQuery { from: Some("2026-12-31"), to: Some("2026-11-01"), ..Default::default() };
+        assert!(query.to_filter(Tokyo).is_err());
+    }
+
+    #[test]
+    fn test_params_round_trip() {
+        for platform in [Platform::Niconico, Platform::Spwn, Platform::Tba, Platform::Youtube, Platform::Zan, Platform::Zaiko, Platform::Other] {
+            assert_eq!(get_platform_from_param(get_param_from_platform(&platform)), Ok(platform));
+        }
+        for format in [LiveFormat::Online, LiveFormat::Irl, LiveFormat::Both] {
+            assert_eq!(get_format_from_param(get_param_from_format(&format)), Ok(format));
+        }
+    }
+}
@@ -1,2 +1,24 @@
 pub mod calendar_parser;
-pub mod models;
\ No newline at end of file
+pub mod filter;
+pub mod models;
+pub mod parse_error;
+pub mod timezone;
+
+#[cfg(feature = "server")]
+pub mod archive;
+#[cfg(feature = "server")]
+pub mod changes;
+#[cfg(feature = "server")]
+pub mod export;
+#[cfg(feature = "server")]
+pub mod feed;
+#[cfg(feature = "server")]
+pub mod persist;
+#[cfg(feature = "server")]
+pub mod snapshot;
+#[cfg(feature = "server")]
+pub mod source;
+#[cfg(feature = "server")]
+pub mod spreadsheet;
+#[cfg(feature = "server")]
+pub mod syndication;
@@ -1,16 +1,20 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use url::Url;
-use rocket::serde::Serialize;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use uuid::Uuid;
+use std::collections::{hash_map::DefaultHasher, BTreeMap};
+use std::hash::{Hash, Hasher};
+use super::parse_error::ParseError;
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Hash, JsonSchema)]
 pub enum LiveFormat {
     Online,
     Irl,
     Both
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Hash, JsonSchema)]
 pub enum Platform {
     Niconico,
     Spwn,
@@ -21,7 +25,7 @@ pub enum Platform {
     Other,
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Hash, JsonSchema)]
 #[serde(tag = "tag", content = "content")]
 pub enum JpyPrice {
     Tbd,
@@ -30,18 +34,292 @@ pub enum JpyPrice {
     MultiTier(i32)
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub enum LiveStatus {
+    Upcoming,
+    Live,
+    Ended
+}
+
+/// How long a concert is assumed to last when the calendar gives no DTEND or DURATION.
+pub const ASSUMED_CONCERT_DURATION_HOURS: i64 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LiveConcert {
     pub id: Uuid,
+    pub uid: String,
     pub title: String, 
     pub format: LiveFormat,
     pub jpy_price: JpyPrice,
     pub platform: Platform,
     pub description: String,
     pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub is_all_day: bool,
+    pub status: LiveStatus,
     pub image_url: Option<Url>,
     pub twitter_url: Option<Url>,
     pub youtube_link: Option<Url>,
     pub ticket_link: Option<Url>,
     pub official_link: Option<Url>
 }
+
+impl LiveConcert {
+    pub fn effective_end_time(&self) -> DateTime<Utc> {
+        self.end_time.unwrap_or(self.start_time + Duration::hours(ASSUMED_CONCERT_DURATION_HOURS))
+    }
+
+    pub fn status_at(&self, now: DateTime<Utc>) -> LiveStatus {
+        if now < self.start_time {
+            LiveStatus::Upcoming
+        } else if now < self.effective_end_time() {
+            LiveStatus::Live
+        } else {
+            LiveStatus::Ended
+        }
+    }
+
+    pub fn with_status_at(mut self, now: DateTime<Utc>) -> Self {
+        self.status = self.status_at(now);
+        self
+    }
+
+    /// Hash of everything the calendar says about the concert, leaving out `status` since that
+    /// only moves with the clock.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (&self.id, &self.uid, &self.title, &self.format, &self.jpy_price, &self.platform, &self.description).hash(&mut hasher);
+        (self.start_time, self.end_time, self.is_all_day).hash(&mut hasher);
+        (&self.image_url, &self.twitter_url, &self.youtube_link, &self.ticket_link, &self.official_link).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Concerts for tests, an upcoming, free, online YouTube concert starting 2030-01-01 10:00 UTC
+/// unless the setters say otherwise.
+#[cfg(test)]
+pub(crate) struct ConcertBuilder(LiveConcert);
+
+// some setters are only used by the modules that need the server feature
+#[cfg(test)]
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+impl ConcertBuilder {
+    pub(crate) fn new() -> Self {
+        use chrono::TimeZone;
+
+        Self(LiveConcert {
+            id: Uuid::from_u128(1),
+            uid: String::from("test@test"),
+            title: String::from("Test Live"),
+            format: LiveFormat::Online,
+            jpy_price: JpyPrice::Free,
+            platform: Platform::Youtube,
+            description: String::new(),
+            start_time: Utc.with_ymd_and_hms(2030, 1, 1, 10, 0, 0).unwrap(),
+            end_time: None,
+            is_all_day: false,
+            status: LiveStatus::Upcoming,
+            image_url: None,
+            twitter_url: None,
+            youtube_link: None,
+            ticket_link: None,
+            official_link: None
+        })
+    }
+
+    pub(crate) fn id(mut self, id: u128) -> Self {
+        self.0.id = Uuid::from_u128(id);
+        self
+    }
+
+    pub(crate) fn uid(mut self, uid: &str) -> Self {
+        self.0.uid = String::from(uid);
+        self
+    }
+
+    pub(crate) fn title(mut self, title: &str) -> Self {
+        self.0.title = String::from(title);
+        self
+    }
+
+    pub(crate) fn format(mut self, format: LiveFormat) -> Self {
+        self.0.format = format;
+        self
+    }
+
+    pub(crate) fn price(mut self, jpy_price: JpyPrice) -> Self {
+        self.0.jpy_price = jpy_price;
+        self
+    }
+
+    pub(crate) fn platform(mut self, platform: Platform) -> Self {
+        self.0.platform = platform;
+        self
+    }
+
+    pub(crate) fn description(mut self, description: &str) -> Self {
+        self.0.description = String::from(description);
+        self
+    }
+
+    pub(crate) fn starts(mut self, start_time: DateTime<Utc>) -> Self {
+        self.0.start_time = start_time;
+        self
+    }
+
+    pub(crate) fn ends(mut self, end_time: DateTime<Utc>) -> Self {
+        self.0.end_time = Some(end_time);
+        self
+    }
+
+    pub(crate) fn status(mut self, status: LiveStatus) -> Self {
+        self.0.status = status;
+        self
+    }
+
+    pub(crate) fn image(mut self, url: &str) -> Self {
+        self.0.image_url = Some(Url::parse(url).unwrap());
+        self
+    }
+
+    pub(crate) fn twitter(mut self, url: &str) -> Self {
+        self.0.twitter_url = Some(Url::parse(url).unwrap());
+        self
+    }
+
+    pub(crate) fn tickets(mut self, url: &str) -> Self {
+        self.0.ticket_link = Some(Url::parse(url).unwrap());
+        self
+    }
+
+    pub(crate) fn official(mut self, url: &str) -> Self {
+        self.0.official_link = Some(Url::parse(url).unwrap());
+        self
+    }
+
+    pub(crate) fn build(self) -> LiveConcert {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConcertPage {
+    pub concerts: Vec<LiveConcert>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize
+}
+
+/// What changed since a cursor handed out by `/concerts/changes`. Both `added` and `updated`
+/// are meant to be upserted, `removed` lists the IDs to drop. With `full_sync` the cursor could
+/// not be answered exactly and `added` holds every concert, anything else the client kept
+/// should be dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConcertChanges {
+    pub cursor: String,
+    pub full_sync: bool,
+    pub added: Vec<LiveConcert>,
+    pub updated: Vec<LiveConcert>,
+    pub removed: Vec<Uuid>,
+}
+
+/// An event of the feed that could not be turned into a [`LiveConcert`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct RejectedEvent {
+    pub feed: String,
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub reason: ParseError
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Diagnostics {
+    pub fetched_at: DateTime<Utc>,
+    /// Whether some of the data was loaded from disk and has not been fetched again since.
+    pub stale: bool,
+    pub parsed_count: usize,
+    pub rejected_count: usize,
+    pub rejected_by_kind: BTreeMap<String, usize>,
+    pub rejected: Vec<RejectedEvent>
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        models::{ConcertBuilder, ConcertChanges, ConcertPage, Diagnostics, JpyPrice, LiveConcert, LiveFormat, LiveStatus, Platform, RejectedEvent},
+        parse_error::ParseError,
+    };
+    use chrono::{TimeZone, Utc};
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::Value;
+    use std::collections::BTreeMap;
+    
+    use uuid::Uuid;
+
+    fn concert(id: u128, jpy_price: JpyPrice) -> LiveConcert {
+        ConcertBuilder::new()
+            .id(id)
+            .uid(&format!("{}@test", id))
+            .title("First Live")
+            .format(LiveFormat::Both)
+            .price(jpy_price)
+            .platform(Platform::Zan)
+            .description("Z-aN link: https://www.zan-live.com/live/detail/10001")
+            .ends(Utc.with_ymd_and_hms(2030, 1, 1, 12, 30, 0).unwrap())
+            .status(LiveStatus::Live)
+            .image("https://example.com/poster.png")
+            .tickets("https://example.com/tickets")
+            .build()
+    }
+
+    /// Serializes, reads the JSON back and serializes again, which has to give the same JSON.
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> Value {
+        let json = serde_json::to_value(value).unwrap();
+        let read: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), json);
+        json
+    }
+
+    #[test]
+    fn test_concert_round_trip() {
+        for price in [JpyPrice::Tbd, JpyPrice::Free, JpyPrice::Fixed(4000), JpyPrice::MultiTier(3500)] {
+            let json = round_trip(&concert(1, price.clone()));
+            let read: LiveConcert = serde_json::from_value(json).unwrap();
+            assert_eq!(read.jpy_price, price);
+            assert_eq!(read.content_hash(), concert(1, price).content_hash());
+        }
+    }
+
+    #[test]
+    fn test_price_is_adjacently_tagged() {
+        assert_eq!(round_trip(&JpyPrice::MultiTier(3500)), serde_json::json!({ "tag": "MultiTier", "content": 3500 }));
+        assert_eq!(round_trip(&JpyPrice::Free), serde_json::json!({ "tag": "Free" }));
+    }
+
+    #[test]
+    fn test_responses_round_trip() {
+        round_trip(&ConcertPage { concerts: vec![concert(1, JpyPrice::Free)], page: 2, per_page: 20, total: 21 });
+        round_trip(&ConcertChanges {
+            cursor: String::from("1-2"),
+            full_sync: false,
+            added: vec![concert(1, JpyPrice::Free)],
+            updated: vec![concert(2, JpyPrice::Tbd)],
+            removed: vec![Uuid::from_u128(3)]
+        });
+        round_trip(&Diagnostics {
+            fetched_at: Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap(),
+            stale: false,
+            parsed_count: 1,
+            rejected_count: 1,
+            rejected_by_kind: BTreeMap::from([(String::from("bad_summary"), 1)]),
+            rejected: vec![RejectedEvent {
+                feed: String::from("teamup"),
+                uid: Some(String::from("broken@test")),
+                summary: Some(String::from("no price")),
+                start_time: None,
+                reason: ParseError::bad_summary("no price").with_uid("broken@test")
+            }]
+        });
+    }
+}
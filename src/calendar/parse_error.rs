@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::fmt;
+
+/// Why an event could not be turned into a [`LiveConcert`](super::models::LiveConcert).
+///
+/// Helpers that only see a piece of the event leave `uid` empty, `get_concert_from_event`
+/// fills it in with [`ParseError::with_uid`] before handing the error on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParseError {
+    MissingProperty { uid: Option<String>, property: String },
+    BadSummary { uid: Option<String>, text: String },
+    UnknownPrice { uid: Option<String>, text: String },
+    UnknownFormat { uid: Option<String>, text: String },
+    UnknownCategory { uid: Option<String>, text: String },
+    BadUrl { uid: Option<String>, text: String },
+    BadTime { uid: Option<String>, text: String },
+}
+
+impl ParseError {
+    pub fn missing_property(property: &str) -> Self {
+        ParseError::MissingProperty { uid: None, property: String::from(property) }
+    }
+
+    pub fn bad_summary(text: &str) -> Self {
+        ParseError::BadSummary { uid: None, text: String::from(text) }
+    }
+
+    pub fn unknown_price(text: &str) -> Self {
+        ParseError::UnknownPrice { uid: None, text: String::from(text) }
+    }
+
+    pub fn unknown_format(text: &str) -> Self {
+        ParseError::UnknownFormat { uid: None, text: String::from(text) }
+    }
+
+    pub fn unknown_category(text: &str) -> Self {
+        ParseError::UnknownCategory { uid: None, text: String::from(text) }
+    }
+
+    pub fn bad_url(text: &str) -> Self {
+        ParseError::BadUrl { uid: None, text: String::from(text) }
+    }
+
+    pub fn bad_time(text: &str) -> Self {
+        ParseError::BadTime { uid: None, text: String::from(text) }
+    }
+
+    /// Short, stable name of the variant, used to count failures per kind.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParseError::MissingProperty { .. } => "missing_property",
+            ParseError::BadSummary { .. } => "bad_summary",
+            ParseError::UnknownPrice { .. } => "unknown_price",
+            ParseError::UnknownFormat { .. } => "unknown_format",
+            ParseError::UnknownCategory { .. } => "unknown_category",
+            ParseError::BadUrl { .. } => "bad_url",
+            ParseError::BadTime { .. } => "bad_time",
+        }
+    }
+
+    pub fn uid(&self) -> Option<&str> {
+        match self {
+            ParseError::MissingProperty { uid, .. }
+            | ParseError::BadSummary { uid, .. }
+            | ParseError::UnknownPrice { uid, .. }
+            | ParseError::UnknownFormat { uid, .. }
+            | ParseError::UnknownCategory { uid, .. }
+            | ParseError::BadUrl { uid, .. }
+            | ParseError::BadTime { uid, .. } => uid.as_deref(),
+        }
+    }
+
+    /// The property name or the text that failed to parse.
+    pub fn text(&self) -> &str {
+        match self {
+            ParseError::MissingProperty { property, .. } => property,
+            ParseError::BadSummary { text, .. }
+            | ParseError::UnknownPrice { text, .. }
+            | ParseError::UnknownFormat { text, .. }
+            | ParseError::UnknownCategory { text, .. }
+            | ParseError::BadUrl { text, .. }
+            | ParseError::BadTime { text, .. } => text,
+        }
+    }
+
+    pub fn with_uid(mut self, event_uid: &str) -> Self {
+        match &mut self {
+            ParseError::MissingProperty { uid, .. }
+            | ParseError::BadSummary { uid, .. }
+            | ParseError::UnknownPrice { uid, .. }
+            | ParseError::UnknownFormat { uid, .. }
+            | ParseError::UnknownCategory { uid, .. }
+            | ParseError::BadUrl { uid, .. }
+            | ParseError::BadTime { uid, .. } => *uid = Some(String::from(event_uid)),
+        }
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            ParseError::MissingProperty { .. } => "missing property",
+            ParseError::BadSummary { .. } => "summary is not \"(price)(format)title\"",
+            ParseError::UnknownPrice { .. } => "unknown price",
+            ParseError::UnknownFormat { .. } => "unknown live format",
+            ParseError::UnknownCategory { .. } => "unknown category",
+            ParseError::BadUrl { .. } => "bad url",
+            ParseError::BadTime { .. } => "bad time",
+        };
+
+        match self.uid() {
+            Some(uid) => write!(f, "event {}: {} \"{}\"", uid, reason, self.text()),
+            None => write!(f, "{} \"{}\"", reason, self.text()),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
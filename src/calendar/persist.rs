@@ -0,0 +1,189 @@
+use super::feed::Feed;
+use super::snapshot::{parse_feed, FeedSnapshot};
+use super::timezone::TimeZoneResolver;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Keeps the last good calendar of every feed on disk, so a restart while upstream is down
+/// still has something to serve.
+///
+/// Each feed gets `<name>.ics` with the calendar as fetched and `<name>.json` with its parse.
+/// The parse is what gets loaded, the calendar is parsed again only when the parse can no
+/// longer be read, e.g. after an upgrade changed its layout.
+#[derive(Debug, Clone)]
+pub struct SnapshotDir {
+    dir: PathBuf,
+}
+
+impl SnapshotDir {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, feed: &Feed, extension: &str) -> PathBuf {
+        let stem: String = feed.name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.{}", stem, extension))
+    }
+
+    pub async fn save(&self, feed: &Feed, calendar_string: &str, snapshot: &FeedSnapshot) -> Result<(), String> {
+        let json = rocket::serde::json::to_string(snapshot).map_err(|e| e.to_string())?;
+
+        tokio::fs::create_dir_all(&self.dir).await
+            .map_err(|e| format!("creating {} failed, {}", self.dir.display(), e))?;
+        write_atomically(&self.path(feed, "ics"), calendar_string).await?;
+        write_atomically(&self.path(feed, "json"), &json).await
+    }
+
+    /// The saved state of the feed marked as stale, or `None` when nothing usable was saved.
+    pub async fn load(&self, feed: &Feed, resolver: &TimeZoneResolver) -> Option<FeedSnapshot> {
+        let json_path = self.path(feed, "json");
+        match tokio::fs::read_to_string(&json_path).await {
+            Ok(json) => match rocket::serde::json::from_str::<FeedSnapshot>(&json) {
+                Ok(snapshot) => return Some(FeedSnapshot { stale: true, ..snapshot }),
+                Err(e) => warn!("reading {} failed, parsing the saved calendar again, the error is {}", json_path.display(), e)
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => warn!("reading {} failed, the error is {}", json_path.display(), e)
+        }
+
+        let ics_path = self.path(feed, "ics");
+        let calendar_string = match tokio::fs::read_to_string(&ics_path).await {
+            Ok(calendar_string) => calendar_string,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("no saved calendar for feed {} in {}", feed.name, self.dir.display());
+                return None;
+            },
+            Err(e) => {
+                warn!("reading {} failed, the error is {}", ics_path.display(), e);
+                return None;
+            }
+        };
+        let fetched_at = tokio::fs::metadata(&ics_path).await
+            .and_then(|m| m.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        match parse_feed(&calendar_string, feed, fetched_at, resolver) {
+            Ok(snapshot) => Some(FeedSnapshot { stale: true, ..snapshot }),
+            Err(e) => {
+                warn!("parsing {} failed, the error is {}", ics_path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Writes next to `path` first and renames, so a crash never leaves half a file behind.
+async fn write_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    tokio::fs::write(&temporary, contents).await
+        .map_err(|e| format!("writing {} failed, {}", temporary.display(), e))?;
+    tokio::fs::rename(&temporary, path).await
+        .map_err(|e| format!("renaming {} to {} failed, {}", temporary.display(), path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        feed::{Feed, FeedLocation},
+        persist::SnapshotDir,
+        snapshot::{parse_feed, CalendarRefresher, SnapshotStore},
+        source::{CalendarSource, FetchError, Fetched, MemorySource},
+        timezone::TimeZoneResolver
+    };
+    use chrono::Utc;
+    use chrono_tz::Asia::Tokyo;
+    use rocket::async_trait;
+    use std::path::PathBuf;
+
+    const FIXTURE: &str = include_str!("../../fixtures/concerts.ics");
+
+    fn feed() -> Feed {
+        Feed { name: String::from("fixture/feed"), location: FeedLocation::Path { path: PathBuf::from("fixtures/concerts.ics") }, default_platform: None }
+    }
+
+    /// A fresh directory under the system temp dir, removed again when the test passes.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("holo-wtf-api-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    struct FailingSource;
+
+    #[async_trait]
+    impl CalendarSource for FailingSource {
+        async fn fetch(&mut self) -> Result<Fetched, FetchError> {
+            Err(FetchError::Timeout)
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_saved_feed_loads_as_stale() {
+        let dir = temp_dir("round-trip");
+        let resolver = TimeZoneResolver::new(Tokyo);
+        let snapshot = parse_feed(FIXTURE, &feed(), Utc::now(), &resolver).unwrap();
+
+        SnapshotDir::new(dir.clone()).save(&feed(), FIXTURE, &snapshot).await.unwrap();
+        assert!(dir.join("fixture_feed.ics").exists());
+
+        let loaded = SnapshotDir::new(dir.clone()).load(&feed(), &resolver).await.unwrap();
+        assert!(loaded.stale);
+        assert_eq!(loaded.fetched_at, snapshot.fetched_at);
+        assert_eq!(loaded.failures, snapshot.failures);
+        assert_eq!(loaded.concerts.iter().map(|c| c.id).collect::<Vec<_>>(), snapshot.concerts.iter().map(|c| c.id).collect::<Vec<_>>());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_unreadable_parse_falls_back_to_calendar() {
+        let dir = temp_dir("fallback");
+        let resolver = TimeZoneResolver::new(Tokyo);
+        let snapshot = parse_feed(FIXTURE, &feed(), Utc::now(), &resolver).unwrap();
+
+        SnapshotDir::new(dir.clone()).save(&feed(), FIXTURE, &snapshot).await.unwrap();
+        std::fs::write(dir.join("fixture_feed.json"), "{\"concerts\": 3}").unwrap();
+
+        let loaded = SnapshotDir::new(dir.clone()).load(&feed(), &resolver).await.unwrap();
+        assert!(loaded.stale);
+        assert_eq!(loaded.concerts.len(), snapshot.concerts.len());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_nothing_saved() {
+        let dir = temp_dir("empty");
+        assert!(SnapshotDir::new(dir).load(&feed(), &TimeZoneResolver::new(Tokyo)).await.is_none());
+    }
+
+    #[rocket::async_test]
+    async fn test_restart_while_upstream_is_down_serves_saved_data() {
+        let dir = temp_dir("restart");
+
+        let mut before_restart = CalendarRefresher::with_sources(vec![(feed(), Box::new(MemorySource::new(FIXTURE)))], TimeZoneResolver::new(Tokyo))
+            .with_snapshot_dir(SnapshotDir::new(dir.clone()));
+        before_restart.refresh(&SnapshotStore::new()).await;
+
+        let mut after_restart = CalendarRefresher::with_sources(vec![(feed(), Box::new(FailingSource))], TimeZoneResolver::new(Tokyo))
+            .with_snapshot_dir(SnapshotDir::new(dir.clone()));
+        let store = SnapshotStore::new();
+        after_restart.load_saved(&store).await;
+        after_restart.refresh(&store).await;
+
+        let snapshot = store.load().unwrap();
+        assert!(snapshot.stale);
+        assert_eq!(snapshot.concerts.len(), 3);
+        assert_eq!(store.failure(), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,550 @@
+use super::calendar_parser::{get_concert_from_event, get_start_time_from_event};
+use super::changes::ChangeLog;
+use super::feed::Feed;
+use super::persist::SnapshotDir;
+use super::source::{get_source_for_feed, CalendarSource, FetchConfig, FetchError, Fetched};
+use super::models::{Diagnostics, LiveConcert, RejectedEvent};
+use super::timezone::TimeZoneResolver;
+use chrono::{DateTime, Utc};
+use icalendar::{
+    parser::{read_calendar, unfold},
+    Calendar,
+    Component
+};
+use log::{info, warn};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// Parsed state of a single feed at one point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FeedSnapshot {
+    pub concerts: Vec<LiveConcert>,
+    pub failures: Vec<RejectedEvent>,
+    pub fetched_at: DateTime<Utc>,
+    /// Loaded from disk on startup and not fetched again since.
+    #[serde(skip)]
+    pub stale: bool,
+}
+
+/// Parsed state of all upstream feeds, merged into one concert list.
+#[derive(Debug)]
+pub struct CalendarSnapshot {
+    pub concerts: Vec<LiveConcert>,
+    pub failures: Vec<RejectedEvent>,
+    /// When the least recently fetched feed was fetched.
+    pub fetched_at: DateTime<Utc>,
+    /// Whether any feed is still served from what was saved on disk.
+    pub stale: bool,
+    /// History of the concert list up to and including this snapshot.
+    pub changes: ChangeLog,
+}
+
+impl CalendarSnapshot {
+    /// Merges feeds in order, a concert whose ID already came from an earlier feed is dropped.
+    pub fn from_feeds<'a>(feeds: impl IntoIterator<Item = &'a FeedSnapshot>) -> Option<Self> {
+        let mut seen = HashSet::new();
+        let mut concerts = vec![];
+        let mut failures = vec![];
+        let mut fetched_at: Option<DateTime<Utc>> = None;
+        let mut stale = false;
+
+        for feed in feeds {
+            concerts.extend(feed.concerts.iter().filter(|c| seen.insert(c.id)).cloned());
+            failures.extend(feed.failures.iter().cloned());
+            fetched_at = Some(fetched_at.map_or(feed.fetched_at, |t| t.min(feed.fetched_at)));
+            stale |= feed.stale;
+        }
+
+        fetched_at.map(|fetched_at| CalendarSnapshot { concerts, failures, fetched_at, stale, changes: ChangeLog::default() })
+    }
+
+    /// When the concerts last changed as far as a client can tell, the last revision of
+    /// `changes` or the last time a concert started or ended, whichever is later. A log that was
+    /// never updated counts as changed at the fetch.
+    pub fn last_modified(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let revised_at = self.changes.revised_at().unwrap_or(self.fetched_at);
+        self.concerts
+            .iter()
+            .flat_map(|c| [c.start_time, c.effective_end_time()])
+            .filter(|t| *t <= now)
+            .fold(revised_at, DateTime::max)
+    }
+
+    /// Number of rejected events per [`ParseError::kind`](super::parse_error::ParseError::kind).
+    pub fn failure_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for failure in self.failures.iter() {
+            *counts.entry(failure.reason.kind().to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            fetched_at: self.fetched_at,
+            stale: self.stale,
+            parsed_count: self.concerts.len(),
+            rejected_count: self.failures.len(),
+            rejected_by_kind: self.failure_counts(),
+            rejected: self.failures.clone()
+        }
+    }
+}
+
+/// Shared handle to the latest snapshot, cheap to clone into background tasks.
+///
+/// Readers get an `Arc` to the snapshot that was current when they asked, so a refresh
+/// swapping in a new snapshot never blocks or invalidates a response being built.
+#[derive(Clone, Default)]
+pub struct SnapshotStore {
+    current: Arc<RwLock<Option<Arc<CalendarSnapshot>>>>,
+    failure: Arc<RwLock<Option<RefreshFailure>>>,
+}
+
+/// Why a refresh ended without any calendar to serve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefreshFailure {
+    /// No feed could be fetched.
+    Fetch,
+    /// At least one feed was fetched, but could not be parsed as a calendar.
+    Parse,
+}
+
+impl SnapshotStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(&self) -> Option<Arc<CalendarSnapshot>> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn store(&self, snapshot: CalendarSnapshot) {
+        *self.current.write().unwrap() = Some(Arc::new(snapshot));
+    }
+
+    /// Records that a refresh ended without a single feed ever having been loaded.
+    pub fn mark_failed(&self, failure: RefreshFailure) {
+        *self.failure.write().unwrap() = Some(failure);
+    }
+
+    pub fn failure(&self) -> Option<RefreshFailure> {
+        *self.failure.read().unwrap()
+    }
+}
+
+pub fn parse_feed(calendar_string: &str, feed: &Feed, fetched_at: DateTime<Utc>, resolver: &TimeZoneResolver) -> Result<FeedSnapshot, String> {
+    let calendar: Calendar = read_calendar(unfold(calendar_string).as_str())?.into();
+    let resolver = resolver.with_calendar_timezones(&calendar);
+    let mut concerts = vec![];
+    let mut failures = vec![];
+
+    for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+        match get_concert_from_event(event, &resolver, feed.default_platform.as_deref()) {
+            Ok(concert) => concerts.push(concert),
+            Err(err) => {
+                warn!("getting concert from event of feed {} failed, the error is {}", feed.name, err);
+                failures.push(RejectedEvent {
+                    feed: feed.name.clone(),
+                    uid: event.get_uid().map(|u| String::from(u.trim())),
+                    summary: event.get_summary().map(|s| String::from(s.trim())),
+                    start_time: get_start_time_from_event(event, &resolver).ok(),
+                    reason: err
+                });
+            }
+        }
+    }
+
+    Ok(FeedSnapshot { concerts, failures, fetched_at, stale: false })
+}
+
+#[derive(Debug)]
+pub enum RefreshError {
+    Fetch(FetchError),
+    Parse(String),
+}
+
+impl RefreshError {
+    pub fn failure(&self) -> RefreshFailure {
+        match self {
+            RefreshError::Fetch(_) => RefreshFailure::Fetch,
+            RefreshError::Parse(_) => RefreshFailure::Parse,
+        }
+    }
+}
+
+impl fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefreshError::Fetch(e) => write!(f, "fetching failed, {}", e),
+            RefreshError::Parse(e) => write!(f, "parsing failed, {}", e),
+        }
+    }
+}
+
+/// Fetches and parses a feed, handing back the calendar as fetched next to its parse, or
+/// `None` when the source reports the calendar unchanged. Only a calendar that parsed is
+/// committed to the source, so it is the one later fetches revalidate against.
+pub async fn fetch_feed(feed: &Feed, source: &mut dyn CalendarSource, resolver: &TimeZoneResolver) -> Result<Option<(String, FeedSnapshot)>, RefreshError> {
+    match source.fetch().await.map_err(RefreshError::Fetch)? {
+        Fetched::Calendar(calendar_string) => {
+            let snapshot = parse_feed(calendar_string.as_str(), feed, Utc::now(), resolver).map_err(RefreshError::Parse)?;
+            source.commit();
+            Ok(Some((calendar_string, snapshot)))
+        },
+        Fetched::NotModified => Ok(None)
+    }
+}
+
+/// Background state of the refresh task: the configured feeds, their sources and the last
+/// good parse of each, optionally mirrored to disk.
+pub struct CalendarRefresher {
+    feeds: Vec<(Feed, Box<dyn CalendarSource>)>,
+    resolver: TimeZoneResolver,
+    last_good: Vec<Option<FeedSnapshot>>,
+    snapshot_dir: Option<SnapshotDir>,
+    changes: ChangeLog,
+}
+
+impl CalendarRefresher {
+    pub fn new(feeds: Vec<Feed>, resolver: TimeZoneResolver, fetch_config: FetchConfig) -> Result<Self, FetchError> {
+        let sources = feeds.into_iter()
+            .map(|feed| {
+                let source = get_source_for_feed(&feed, fetch_config)?;
+                Ok((feed, source))
+            })
+            .collect::<Result<_, FetchError>>()?;
+        Ok(Self::with_sources(sources, resolver))
+    }
+
+    pub fn with_sources(feeds: Vec<(Feed, Box<dyn CalendarSource>)>, resolver: TimeZoneResolver) -> Self {
+        let last_good = vec![None; feeds.len()];
+        Self { feeds, resolver, last_good, snapshot_dir: None, changes: ChangeLog::new(Utc::now()) }
+    }
+
+    /// Saves every good fetch to `dir` and lets [`CalendarRefresher::load_saved`] read it back.
+    pub fn with_snapshot_dir(mut self, dir: SnapshotDir) -> Self {
+        self.snapshot_dir = Some(dir);
+        self
+    }
+
+    /// Fills `store` with what was saved on disk before the first fetch, marked as stale.
+    pub async fn load_saved(&mut self, store: &SnapshotStore) {
+        let Some(dir) = &self.snapshot_dir else {
+            return;
+        };
+
+        for ((feed, _), last_good) in self.feeds.iter().zip(self.last_good.iter_mut()) {
+            if last_good.is_none() {
+                *last_good = dir.load(feed, &self.resolver).await;
+            }
+        }
+
+        if let Some(snapshot) = CalendarSnapshot::from_feeds(self.last_good.iter().flatten()) {
+            info!("loaded {} saved concerts fetched at {}", snapshot.concerts.len(), snapshot.fetched_at);
+            self.publish(snapshot, store);
+        }
+    }
+
+    /// Fetches every feed and swaps the merged result into `store`. A feed that fails keeps
+    /// contributing its previous data, so one broken feed never empties the list.
+    pub async fn refresh(&mut self, store: &SnapshotStore) {
+        let mut failure = RefreshFailure::Fetch;
+
+        for ((feed, source), last_good) in self.feeds.iter_mut().zip(self.last_good.iter_mut()) {
+            match fetch_feed(feed, source.as_mut(), &self.resolver).await {
+                Ok(Some((calendar_string, snapshot))) => {
+                    info!("feed {} refreshed, {} concerts parsed, {} events rejected", feed.name, snapshot.concerts.len(), snapshot.failures.len());
+                    if let Some(dir) = &self.snapshot_dir {
+                        if let Err(e) = dir.save(feed, &calendar_string, &snapshot).await {
+                            warn!("saving feed {} failed, the error is {}", feed.name, e);
+                        }
+                    }
+                    *last_good = Some(snapshot);
+                },
+                Ok(None) => match last_good {
+                    Some(snapshot) => {
+                        info!("feed {} not modified, keeping its {} concerts", feed.name, snapshot.concerts.len());
+                        snapshot.fetched_at = Utc::now();
+                        snapshot.stale = false;
+                    },
+                    None => warn!("feed {} reported not modified before it was ever fetched", feed.name)
+                },
+                Err(e) => {
+                    warn!("refreshing feed {} failed, keeping its previous data, the error is {}", feed.name, e);
+                    if e.failure() == RefreshFailure::Parse {
+                        failure = RefreshFailure::Parse;
+                    }
+                }
+            }
+        }
+
+        if let Some(snapshot) = CalendarSnapshot::from_feeds(self.last_good.iter().flatten()) {
+            info!("calendar refreshed, {} concerts in total, failures by kind {:?}", snapshot.concerts.len(), snapshot.failure_counts());
+            self.publish(snapshot, store);
+        } else {
+            store.mark_failed(failure);
+        }
+    }
+
+    /// Records the snapshot in the change log and swaps it into `store`.
+    fn publish(&mut self, snapshot: CalendarSnapshot, store: &SnapshotStore) {
+        self.changes.update(&snapshot.concerts, Utc::now());
+        store.store(CalendarSnapshot { changes: self.changes.clone(), ..snapshot });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        changes::ChangeLog,
+        models::{LiveStatus, Platform, RejectedEvent},
+        parse_error::ParseError,
+        feed::{Feed, FeedLocation},
+        snapshot::{parse_feed, CalendarRefresher, CalendarSnapshot, FeedSnapshot, RefreshFailure, SnapshotStore},
+        source::{tests::{ok_response, serve}, CalendarSource, FetchConfig, FetchError, Fetched, HttpSource},
+        timezone::TimeZoneResolver
+    };
+    use rocket::async_trait;
+    use std::collections::VecDeque;
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use chrono_tz::Asia::Tokyo;
+    use url::Url;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//test//test//EN\r
+BEGIN:VEVENT\r
+UID:first@test\r
+DTSTART:20300101T100000Z\r
+DTEND:20300101T120000Z\r
+SUMMARY:(¥3500+)(🌐)First Live\r
+CATEGORIES:SPWN\r
+DESCRIPTION:SPWN link: https://virtual.spwn.jp/events/first\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:broken@test\r
+DTSTART:20300102T100000Z\r
+SUMMARY:no price or format\r
+CATEGORIES:SPWN\r
+DESCRIPTION:nothing\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    const REORDERED_CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//test//test//EN\r
+BEGIN:VEVENT\r
+UID:second@test\r
+DTSTART:20300105T100000Z\r
+SUMMARY:(Free)(🌐🪑)Second Live\r
+CATEGORIES:YouTube\r
+DESCRIPTION:YouTube link: https://www.youtube.com/watch?v=JiOw0LhFYtQ\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:first@test\r
+DTSTART:20300101T100000Z\r
+DTEND:20300101T120000Z\r
+SUMMARY:(¥3500+)(🌐)First Live\r
+CATEGORIES:SPWN\r
+DESCRIPTION:SPWN link: https://virtual.spwn.jp/events/first\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn feed() -> Feed {
+        Feed { name: String::from("test"), location: FeedLocation::Url { url: Url::parse("https://example.com/test.ics").unwrap() }, default_platform: None }
+    }
+
+    fn parse(calendar: &str, feed: &Feed, fetched_at: DateTime<Utc>) -> FeedSnapshot {
+        parse_feed(calendar, feed, fetched_at, &TimeZoneResolver::new(Tokyo)).unwrap()
+    }
+
+    /// Hands out the given fetch results in order.
+    struct ScriptedSource {
+        results: VecDeque<Result<Fetched, FetchError>>,
+    }
+
+    #[async_trait]
+    impl CalendarSource for ScriptedSource {
+        async fn fetch(&mut self) -> Result<Fetched, FetchError> {
+            self.results.pop_front().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_parse_feed_skips_broken_events() {
+        let fetched_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        let snapshot = parse(CALENDAR, &feed(), fetched_at);
+
+        assert_eq!(snapshot.fetched_at, fetched_at);
+        assert_eq!(snapshot.concerts.len(), 1);
+        assert_eq!(snapshot.concerts[0].title, "First Live");
+        assert_eq!(snapshot.failures, vec![RejectedEvent {
+            feed: String::from("test"),
+            uid: Some(String::from("broken@test")),
+            summary: Some(String::from("no price or format")),
+            start_time: Some(Utc.with_ymd_and_hms(2030, 1, 2, 10, 0, 0).unwrap()),
+            reason: ParseError::BadSummary { uid: Some(String::from("broken@test")), text: String::from("no price or format") }
+        }]);
+
+        let merged = CalendarSnapshot::from_feeds([&snapshot]).unwrap();
+        assert_eq!(merged.failure_counts().get("bad_summary"), Some(&1));
+    }
+
+    #[test]
+    fn test_store_swaps_snapshot() {
+        let store = SnapshotStore::new();
+        assert!(store.load().is_none());
+
+        let first = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        store.store(CalendarSnapshot { concerts: vec![], failures: vec![], fetched_at: first, stale: false, changes: ChangeLog::default() });
+        let held = store.load().unwrap();
+
+        let second = Utc.with_ymd_and_hms(2029, 1, 2, 0, 0, 0).unwrap();
+        store.store(CalendarSnapshot { concerts: vec![], failures: vec![], fetched_at: second, stale: false, changes: ChangeLog::default() });
+
+        assert_eq!(held.fetched_at, first);
+        assert_eq!(store.load().unwrap().fetched_at, second);
+    }
+
+    #[test]
+    fn test_concert_ids_are_stable_across_fetches() {
+        let fetched_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        let first_fetch = parse(CALENDAR, &feed(), fetched_at);
+        let second_fetch = parse(CALENDAR, &feed(), fetched_at);
+
+        assert_eq!(first_fetch.concerts[0].id, second_fetch.concerts[0].id);
+        assert_eq!(first_fetch.concerts[0].uid, "first@test");
+    }
+
+    #[test]
+    fn test_concert_ids_are_stable_across_reordering() {
+        let fetched_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        let original = parse(CALENDAR, &feed(), fetched_at);
+        let reordered = parse(REORDERED_CALENDAR, &feed(), fetched_at);
+
+        let first_live = reordered.concerts.iter().find(|c| c.uid == "first@test").unwrap();
+        let second_live = reordered.concerts.iter().find(|c| c.uid == "second@test").unwrap();
+
+        assert_eq!(first_live.id, original.concerts[0].id);
+        assert_ne!(first_live.id, second_live.id);
+    }
+
+    #[test]
+    fn test_status_follows_start_and_end() {
+        let fetched_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        let live = &parse(CALENDAR, &feed(), fetched_at).concerts[0];
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 10, 0, 0).unwrap();
+
+        assert_eq!(live.end_time, Some(start + Duration::hours(2)));
+        assert_eq!(live.status_at(start - Duration::minutes(1)), LiveStatus::Upcoming);
+        assert_eq!(live.status_at(start + Duration::hours(1)), LiveStatus::Live);
+        assert_eq!(live.status_at(start + Duration::hours(2)), LiveStatus::Ended);
+    }
+
+    #[test]
+    fn test_default_platform_of_feed() {
+        let calendar = CALENDAR.replace("CATEGORIES:SPWN\r\n", "");
+        let fetched_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+
+        assert!(parse(&calendar, &feed(), fetched_at).concerts.is_empty());
+
+        let zan_feed = Feed { default_platform: Some(String::from("Z-aN")), ..feed() };
+        assert_eq!(parse(&calendar, &zan_feed, fetched_at).concerts[0].platform, Platform::Zan);
+    }
+
+    #[test]
+    fn test_merged_feeds_drop_duplicates() {
+        let first = parse(CALENDAR, &feed(), Utc.with_ymd_and_hms(2029, 1, 2, 0, 0, 0).unwrap());
+        let second = parse(REORDERED_CALENDAR, &Feed { name: String::from("staging"), ..feed() }, Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap());
+        let merged = CalendarSnapshot::from_feeds([&first, &second]).unwrap();
+
+        let uids: Vec<_> = merged.concerts.iter().map(|c| c.uid.as_str()).collect();
+        assert_eq!(uids, vec!["first@test", "second@test"]);
+        assert_eq!(merged.fetched_at, Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_last_modified_follows_revisions_and_status() {
+        let fetched_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        let mut snapshot = CalendarSnapshot::from_feeds([&parse(CALENDAR, &feed(), fetched_at)]).unwrap();
+        assert_eq!(snapshot.last_modified(fetched_at), fetched_at);
+
+        let revised_at = Utc.with_ymd_and_hms(2028, 12, 31, 0, 0, 0).unwrap();
+        snapshot.changes = ChangeLog::new(revised_at);
+        snapshot.changes.update(&snapshot.concerts, revised_at);
+        assert_eq!(snapshot.last_modified(fetched_at), revised_at);
+
+        let first_end = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(snapshot.last_modified(first_end + Duration::hours(1)), first_end);
+    }
+
+    #[rocket::async_test]
+    async fn test_unmodified_and_failed_feeds_keep_their_concerts() {
+        let source = ScriptedSource { results: VecDeque::from([
+            Ok(Fetched::Calendar(String::from(CALENDAR))),
+            Ok(Fetched::NotModified),
+            Err(FetchError::Timeout),
+        ]) };
+        let mut refresher = CalendarRefresher::with_sources(vec![(feed(), Box::new(source))], TimeZoneResolver::new(Tokyo));
+        let store = SnapshotStore::new();
+
+        refresher.refresh(&store).await;
+        let first_fetch = store.load().unwrap().fetched_at;
+
+        refresher.refresh(&store).await;
+        let not_modified = store.load().unwrap();
+        assert_eq!(not_modified.concerts.len(), 1);
+        assert!(not_modified.fetched_at >= first_fetch);
+
+        refresher.refresh(&store).await;
+        assert_eq!(store.load().unwrap().concerts.len(), 1);
+    }
+
+    #[rocket::async_test]
+    async fn test_failing_first_refresh_marks_fetch_failure() {
+        let source = ScriptedSource { results: VecDeque::from([Err(FetchError::Status(503))]) };
+        let mut refresher = CalendarRefresher::with_sources(vec![(feed(), Box::new(source))], TimeZoneResolver::new(Tokyo));
+        let store = SnapshotStore::new();
+
+        refresher.refresh(&store).await;
+        assert!(store.load().is_none());
+        assert_eq!(store.failure(), Some(RefreshFailure::Fetch));
+    }
+
+    #[rocket::async_test]
+    async fn test_unparseable_calendar_marks_parse_failure() {
+        let source = ScriptedSource { results: VecDeque::from([Ok(Fetched::Calendar(String::from("<html>maintenance</html>")))]) };
+        let mut refresher = CalendarRefresher::with_sources(vec![(feed(), Box::new(source))], TimeZoneResolver::new(Tokyo));
+        let store = SnapshotStore::new();
+
+        refresher.refresh(&store).await;
+        assert!(store.load().is_none());
+        assert_eq!(store.failure(), Some(RefreshFailure::Parse));
+    }
+
+    #[rocket::async_test]
+    async fn test_unparseable_calendar_is_not_revalidated() {
+        let maintenance = ok_response("etag: \"maintenance\"\r\n", "<html>maintenance</html>");
+        let (url, server) = serve(vec![maintenance.clone(), maintenance, ok_response("etag: \"v1\"\r\n", CALENDAR)]).await;
+        let source = HttpSource::new(url, FetchConfig::default()).unwrap();
+        let mut refresher = CalendarRefresher::with_sources(vec![(feed(), Box::new(source))], TimeZoneResolver::new(Tokyo));
+        let store = SnapshotStore::new();
+
+        refresher.refresh(&store).await;
+        refresher.refresh(&store).await;
+        assert!(store.load().is_none());
+        assert_eq!(store.failure(), Some(RefreshFailure::Parse));
+
+        refresher.refresh(&store).await;
+        assert!(!store.load().unwrap().concerts.is_empty());
+
+        let heads = server.await.unwrap();
+        assert!(heads.iter().all(|head| !head.contains("if-none-match")));
+    }
+}
@@ -0,0 +1,498 @@
+use super::feed::{Feed, FeedLocation};
+use log::warn;
+use reqwest::{
+    header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Response,
+    StatusCode
+};
+use rocket::async_trait;
+use rocket::serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+use url::Url;
+
+/// Media types a calendar may come back as, anything else (usually an HTML error page) is refused.
+const ACCEPTED_CONTENT_TYPES: [&str; 3] = ["text/calendar", "text/plain", "application/octet-stream"];
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_read_timeout() -> u64 {
+    30
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_retry_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_max_body_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+/// How upstream calendars are fetched over HTTP, the `fetch` table of the Rocket configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FetchConfig {
+    /// Seconds to wait for the connection to the upstream server.
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Seconds to wait for the response head and then for each chunk of the body.
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout: u64,
+    /// Further attempts after a timeout, connection error, 429 or 5xx.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Wait before the first retry in milliseconds, doubled on every further retry.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Upper bound of the wait between two retries in milliseconds.
+    #[serde(default = "default_max_retry_backoff_ms")]
+    pub max_retry_backoff_ms: u64,
+    /// Calendars larger than this are refused instead of being read into memory.
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: default_connect_timeout(),
+            read_timeout: default_read_timeout(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            max_retry_backoff_ms: default_max_retry_backoff_ms(),
+            max_body_bytes: default_max_body_bytes()
+        }
+    }
+}
+
+impl FetchConfig {
+    /// Wait before retry number `retry`, counting from 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.retry_backoff_ms.saturating_mul(2u64.saturating_pow(retry));
+        Duration::from_millis(backoff.min(self.max_retry_backoff_ms))
+    }
+}
+
+/// What a source handed back on one fetch.
+#[derive(Debug, PartialEq)]
+pub enum Fetched {
+    Calendar(String),
+    /// The calendar has not changed since the previous fetch, there is nothing new to parse.
+    NotModified,
+}
+
+/// Why a source could not hand back a calendar.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchError {
+    Timeout,
+    Connection(String),
+    Status(u16),
+    TooLarge { limit: u64 },
+    UnexpectedContentType(String),
+    NotUtf8,
+    Io(String),
+    /// The HTTP client itself could not be set up, e.g. without TLS support on the system.
+    Client(String),
+}
+
+impl FetchError {
+    /// Whether trying again later has a chance of succeeding.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FetchError::Timeout | FetchError::Connection(_) => true,
+            FetchError::Status(status) => *status == 429 || *status >= 500,
+            _ => false
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            FetchError::Timeout
+        } else {
+            FetchError::Connection(e.to_string())
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Timeout => write!(f, "timed out"),
+            FetchError::Connection(e) => write!(f, "connection failed, {}", e),
+            FetchError::Status(status) => write!(f, "upstream answered with status {}", status),
+            FetchError::TooLarge { limit } => write!(f, "calendar is larger than {} bytes", limit),
+            FetchError::UnexpectedContentType(content_type) => write!(f, "unexpected content type \"{}\"", content_type),
+            FetchError::NotUtf8 => write!(f, "calendar is not valid UTF-8"),
+            FetchError::Io(e) => write!(f, "{}", e),
+            FetchError::Client(e) => write!(f, "HTTP client could not be set up, {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// Somewhere the raw iCalendar text of a feed can be read from.
+#[async_trait]
+pub trait CalendarSource: Send + Sync {
+    async fn fetch(&mut self) -> Result<Fetched, FetchError>;
+
+    /// Called once the calendar of the last fetch parsed. A source that revalidates only
+    /// asks upstream whether a calendar changed after this, so a calendar that failed to parse
+    /// is fetched again in full instead of coming back as not modified.
+    fn commit(&mut self) {}
+}
+
+/// Fetches a feed over HTTP, sending back the `ETag` and `Last-Modified` of the last response
+/// that was committed so an unchanged calendar comes back as an empty 304.
+pub struct HttpSource {
+    url: Url,
+    client: reqwest::Client,
+    config: FetchConfig,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Validators of the last calendar fetched, kept once it is committed.
+    pending: (Option<String>, Option<String>),
+}
+
+impl HttpSource {
+    pub fn new(url: Url, config: FetchConfig) -> Result<Self, FetchError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| FetchError::Client(e.to_string()))?;
+        Ok(Self { url, client, config, etag: None, last_modified: None, pending: (None, None) })
+    }
+
+    async fn fetch_once(&self) -> Result<(Fetched, Option<String>, Option<String>), FetchError> {
+        // no timeout on the request as a whole, reqwest would count the body in it and cut off
+        // a large calendar that is still arriving steadily
+        let read_timeout = Duration::from_secs(self.config.read_timeout);
+        let mut request = self.client.get(self.url.as_str());
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = tokio::time::timeout(read_timeout, request.send()).await.map_err(|_| FetchError::Timeout)??;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok((Fetched::NotModified, None, None));
+        }
+        if !response.status().is_success() {
+            return Err(FetchError::Status(response.status().as_u16()));
+        }
+        check_content_type(&response)?;
+
+        let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok()).map(String::from);
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let calendar = read_body(response, read_timeout, self.config.max_body_bytes).await?;
+
+        Ok((Fetched::Calendar(calendar), etag, last_modified))
+    }
+}
+
+#[async_trait]
+impl CalendarSource for HttpSource {
+    async fn fetch(&mut self) -> Result<Fetched, FetchError> {
+        let mut retry = 0;
+        loop {
+            match self.fetch_once().await {
+                Ok((fetched, etag, last_modified)) => {
+                    // only hold on to the validators once a body has arrived in full
+                    if let Fetched::Calendar(_) = fetched {
+                        self.pending = (etag, last_modified);
+                    }
+                    return Ok(fetched);
+                },
+                Err(e) if e.is_retryable() && retry < self.config.max_retries => {
+                    let backoff = self.config.backoff(retry);
+                    warn!("fetching {} failed, retrying in {:?}, the error is {}", self.url, backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    retry += 1;
+                },
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    fn commit(&mut self) {
+        (self.etag, self.last_modified) = std::mem::take(&mut self.pending);
+    }
+}
+
+/// Refuses responses that are clearly not a calendar, a missing content type is let through.
+fn check_content_type(response: &Response) -> Result<(), FetchError> {
+    let content_type = match response.headers().get(CONTENT_TYPE) {
+        Some(value) => value.to_str().map_err(|_| FetchError::UnexpectedContentType(String::from("<not ascii>")))?.to_lowercase(),
+        None => return Ok(())
+    };
+
+    let mut parts = content_type.split(';').map(str::trim);
+    let media_type = parts.next().unwrap_or_default();
+    if !ACCEPTED_CONTENT_TYPES.contains(&media_type) {
+        return Err(FetchError::UnexpectedContentType(content_type.clone()));
+    }
+
+    let charset = parts
+        .filter_map(|p| p.strip_prefix("charset="))
+        .map(|c| c.trim_matches('"'))
+        .next();
+    match charset {
+        None | Some("utf-8") | Some("utf8") | Some("us-ascii") => Ok(()),
+        Some(_) => Err(FetchError::UnexpectedContentType(content_type.clone()))
+    }
+}
+
+/// Reads the body chunk by chunk, giving up once it grows past `max_bytes` or a chunk takes
+/// longer than `read_timeout` to arrive.
+async fn read_body(mut response: Response, read_timeout: Duration, max_bytes: u64) -> Result<String, FetchError> {
+    if response.content_length().is_some_and(|length| length > max_bytes) {
+        return Err(FetchError::TooLarge { limit: max_bytes });
+    }
+
+    let mut body = vec![];
+    while let Some(chunk) = tokio::time::timeout(read_timeout, response.chunk()).await.map_err(|_| FetchError::Timeout)?? {
+        if (body.len() + chunk.len()) as u64 > max_bytes {
+            return Err(FetchError::TooLarge { limit: max_bytes });
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(body).map_err(|_| FetchError::NotUtf8)
+}
+
+/// Reads a local `.ics` file on every fetch, so edits show up on the next refresh.
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl CalendarSource for FileSource {
+    async fn fetch(&mut self) -> Result<Fetched, FetchError> {
+        tokio::fs::read_to_string(&self.path).await
+            .map(Fetched::Calendar)
+            .map_err(|e| FetchError::Io(format!("reading {} failed, {}", self.path.display(), e)))
+    }
+}
+
+/// Serves a fixed calendar, mainly so tests can run the whole app without network.
+pub struct MemorySource {
+    calendar: String,
+}
+
+impl MemorySource {
+    pub fn new(calendar: impl Into<String>) -> Self {
+        Self { calendar: calendar.into() }
+    }
+}
+
+#[async_trait]
+impl CalendarSource for MemorySource {
+    async fn fetch(&mut self) -> Result<Fetched, FetchError> {
+        Ok(Fetched::Calendar(self.calendar.clone()))
+    }
+}
+
+pub fn get_source_for_feed(feed: &Feed, config: FetchConfig) -> Result<Box<dyn CalendarSource>, FetchError> {
+    match &feed.location {
+        FeedLocation::Url { url } => Ok(Box::new(HttpSource::new(url.clone(), config)?)),
+        FeedLocation::Path { path } => Ok(Box::new(FileSource::new(path.clone()))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::calendar::source::{CalendarSource, FetchConfig, FetchError, Fetched, FileSource, HttpSource};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use url::Url;
+
+    const BODY: &str = "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n";
+
+    pub(crate) fn ok_response(headers: &str, body: &str) -> Option<String> {
+        Some(format!("HTTP/1.1 200 OK\r\n{}content-length: {}\r\nconnection: close\r\n\r\n{}", headers, body.len(), body))
+    }
+
+    fn status_response(status: &str) -> Option<String> {
+        Some(format!("HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", status))
+    }
+
+    /// Answers one request per entry of `responses` on a local port, `None` never answers,
+    /// and hands back the request heads it saw.
+    pub(crate) async fn serve(responses: Vec<Option<String>>) -> (Url, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/calendar.ics", listener.local_addr().unwrap())).unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut heads = vec![];
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = vec![0; 4096];
+                let read = stream.read(&mut buffer).await.unwrap();
+                heads.push(String::from_utf8_lossy(&buffer[..read]).to_lowercase());
+
+                match response {
+                    Some(response) => stream.write_all(response.as_bytes()).await.unwrap(),
+                    None => tokio::time::sleep(Duration::from_secs(5)).await
+                }
+            }
+            heads
+        });
+
+        (url, server)
+    }
+
+    fn config() -> FetchConfig {
+        FetchConfig { read_timeout: 1, retry_backoff_ms: 1, ..Default::default() }
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_sends_validators_back() {
+        let (url, server) = serve(vec![
+            ok_response("etag: \"v1\"\r\nlast-modified: Wed, 01 Jan 2030 00:00:00 GMT\r\n", BODY),
+            status_response("304 Not Modified"),
+        ]).await;
+        let mut source = HttpSource::new(url, config()).unwrap();
+
+        assert_eq!(source.fetch().await, Ok(Fetched::Calendar(String::from(BODY))));
+        source.commit();
+        assert_eq!(source.fetch().await, Ok(Fetched::NotModified));
+
+        let heads = server.await.unwrap();
+        assert!(!heads[0].contains("if-none-match"));
+        assert!(heads[1].contains("if-none-match: \"v1\""));
+        assert!(heads[1].contains("if-modified-since: wed, 01 jan 2030 00:00:00 gmt"));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_refetches_an_uncommitted_calendar_in_full() {
+        let (url, server) = serve(vec![
+            ok_response("etag: \"v1\"\r\n", "<html>maintenance</html>"),
+            ok_response("etag: \"v2\"\r\n", BODY),
+        ]).await;
+        let mut source = HttpSource::new(url, config()).unwrap();
+
+        assert!(source.fetch().await.is_ok());
+        assert_eq!(source.fetch().await, Ok(Fetched::Calendar(String::from(BODY))));
+
+        let heads = server.await.unwrap();
+        assert!(!heads[1].contains("if-none-match"));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_retries_server_errors() {
+        let (url, server) = serve(vec![
+            status_response("503 Service Unavailable"),
+            status_response("502 Bad Gateway"),
+            ok_response("content-type: text/calendar; charset=utf-8\r\n", BODY),
+        ]).await;
+
+        assert_eq!(HttpSource::new(url, config()).unwrap().fetch().await, Ok(Fetched::Calendar(String::from(BODY))));
+        assert_eq!(server.await.unwrap().len(), 3);
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_gives_up_after_max_retries() {
+        let (url, _server) = serve(vec![status_response("500 Internal Server Error"); 2]).await;
+        let config = FetchConfig { max_retries: 1, ..config() };
+
+        assert_eq!(HttpSource::new(url, config).unwrap().fetch().await, Err(FetchError::Status(500)));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_does_not_retry_client_errors() {
+        let (url, _server) = serve(vec![status_response("404 Not Found")]).await;
+        assert_eq!(HttpSource::new(url, config()).unwrap().fetch().await, Err(FetchError::Status(404)));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_times_out() {
+        let (url, _server) = serve(vec![None]).await;
+        let config = FetchConfig { max_retries: 0, ..config() };
+
+        assert_eq!(HttpSource::new(url, config).unwrap().fetch().await, Err(FetchError::Timeout));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_refuses_large_bodies() {
+        let (url, _server) = serve(vec![ok_response("", BODY)]).await;
+        let config = FetchConfig { max_body_bytes: 8, ..config() };
+
+        assert_eq!(HttpSource::new(url, config).unwrap().fetch().await, Err(FetchError::TooLarge { limit: 8 }));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_refuses_html_and_other_charsets() {
+        let (url, _server) = serve(vec![
+            ok_response("content-type: text/html\r\n", "<html></html>"),
+            ok_response("content-type: text/calendar; charset=Shift_JIS\r\n", BODY),
+        ]).await;
+        let mut source = HttpSource::new(url, config()).unwrap();
+
+        assert_eq!(source.fetch().await, Err(FetchError::UnexpectedContentType(String::from("text/html"))));
+        assert_eq!(source.fetch().await, Err(FetchError::UnexpectedContentType(String::from("text/calendar; charset=shift_jis"))));
+    }
+
+    #[rocket::async_test]
+    async fn test_http_source_reads_a_slow_body_past_the_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/calendar.ics", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut vec![0; 4096]).await.unwrap();
+            let head = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n", BODY.len());
+            stream.write_all(head.as_bytes()).await.unwrap();
+            // a line every 400ms, 1.6s for the whole body against a read timeout of 1s
+            for line in BODY.split_inclusive('\n').chain(["", ""]) {
+                tokio::time::sleep(Duration::from_millis(400)).await;
+                stream.write_all(line.as_bytes()).await.unwrap();
+                stream.flush().await.unwrap();
+            }
+        });
+
+        assert_eq!(HttpSource::new(url, config()).unwrap().fetch().await, Ok(Fetched::Calendar(String::from(BODY))));
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let config = FetchConfig { retry_backoff_ms: 500, max_retry_backoff_ms: 3000, ..Default::default() };
+        let backoffs: Vec<_> = (0..5).map(|retry| config.backoff(retry).as_millis()).collect();
+        assert_eq!(backoffs, vec![500, 1000, 2000, 3000, 3000]);
+    }
+
+    #[rocket::async_test]
+    async fn test_file_source_reads_fixture() {
+        let fetched = FileSource::new(PathBuf::from("fixtures/concerts.ics")).fetch().await.unwrap();
+        assert!(matches!(fetched, Fetched::Calendar(calendar) if calendar.contains("UID:fixture-spwn@holo-wtf-api")));
+    }
+
+    #[rocket::async_test]
+    async fn test_missing_file_is_an_error() {
+        let result = FileSource::new(PathBuf::from("fixtures/missing.ics")).fetch().await;
+        assert!(matches!(result, Err(FetchError::Io(e)) if e.contains("fixtures/missing.ics")));
+    }
+}
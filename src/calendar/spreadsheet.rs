@@ -0,0 +1,168 @@
+use super::models::{JpyPrice, LiveConcert};
+use chrono::{DateTime, Utc};
+use chrono_tz::Asia::Tokyo;
+use url::Url;
+
+/// Columns of the flattened concert, in order.
+pub const COLUMNS: [&str; 19] = [
+    "id", "title", "status", "platform", "format", "price_kind", "price_yen",
+    "start_time_utc", "start_time_jst", "end_time_utc", "end_time_jst", "is_all_day",
+    "official_link", "ticket_link", "youtube_link", "twitter_url", "image_url", "description", "uid",
+];
+
+/// Excel only reads a file as UTF-8, and so gets the Japanese titles right, when it starts
+/// with a byte order mark.
+const BYTE_ORDER_MARK: &str = "\u{feff}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delimiter {
+    Comma,
+    Tab
+}
+
+/// The `tag` and the yen amount of the price, with the amount empty while it is not known.
+pub fn get_price_columns(price: &JpyPrice) -> (&'static str, Option<i32>) {
+    match price {
+        JpyPrice::Tbd => ("Tbd", None),
+        JpyPrice::Free => ("Free", Some(0)),
+        JpyPrice::Fixed(yen) => ("Fixed", Some(*yen)),
+        JpyPrice::MultiTier(yen) => ("MultiTier", Some(*yen)),
+    }
+}
+
+/// A time the way spreadsheets recognise it on their own, without a `T` or an offset.
+fn get_cell_from_time(time: DateTime<Utc>) -> (String, String) {
+    (
+        time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        time.with_timezone(&Tokyo).format("%Y-%m-%d %H:%M:%S").to_string()
+    )
+}
+
+fn get_cell_from_url(url: &Option<Url>) -> String {
+    url.as_ref().map(Url::to_string).unwrap_or_default()
+}
+
+/// Quotes a CSV cell as RFC 4180 does, or flattens a TSV cell that has no quoting at all. A
+/// leading `=`, `+`, `-` or `@` would make a spreadsheet run the text as a formula, so those
+/// get a `'` in front.
+fn escape_cell(cell: &str, delimiter: Delimiter) -> String {
+    let cell = if cell.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", cell)
+    } else {
+        cell.to_string()
+    };
+
+    match delimiter {
+        Delimiter::Comma if cell.contains([',', '"', '\r', '\n']) => format!("\"{}\"", cell.replace('"', "\"\"")),
+        Delimiter::Comma => cell,
+        Delimiter::Tab => cell.replace(['\t', '\r', '\n'], " "),
+    }
+}
+
+pub fn get_row_from_concert(concert: &LiveConcert) -> Vec<String> {
+    let (price_kind, price_yen) = get_price_columns(&concert.jpy_price);
+    let (start_utc, start_jst) = get_cell_from_time(concert.start_time);
+    let (end_utc, end_jst) = concert.end_time.map(get_cell_from_time).unwrap_or_default();
+
+    // Debug of the unit enums is their variant name, the same as in the JSON
+    vec![
+        concert.id.to_string(),
+        concert.title.clone(),
+        format!("{:?}", concert.status),
+        format!("{:?}", concert.platform),
+        format!("{:?}", concert.format),
+        price_kind.to_string(),
+        price_yen.map(|yen| yen.to_string()).unwrap_or_default(),
+        start_utc,
+        start_jst,
+        end_utc,
+        end_jst,
+        concert.is_all_day.to_string(),
+        get_cell_from_url(&concert.official_link),
+        get_cell_from_url(&concert.ticket_link),
+        get_cell_from_url(&concert.youtube_link),
+        get_cell_from_url(&concert.twitter_url),
+        get_cell_from_url(&concert.image_url),
+        concert.description.clone(),
+        concert.uid.clone(),
+    ]
+}
+
+/// The concerts as a table with a header row, one line per concert.
+pub fn get_table_from_concerts(concerts: &[LiveConcert], delimiter: Delimiter) -> String {
+    let separator = match delimiter {
+        Delimiter::Comma => ",",
+        Delimiter::Tab => "\t",
+    };
+
+    let mut table = String::from(BYTE_ORDER_MARK);
+    table.push_str(&COLUMNS.join(separator));
+    table.push_str("\r\n");
+    for concert in concerts {
+        let row: Vec<String> = get_row_from_concert(concert)
+            .iter()
+            .map(|cell| escape_cell(cell, delimiter))
+            .collect();
+        table.push_str(&row.join(separator));
+        table.push_str("\r\n");
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        models::{ConcertBuilder, JpyPrice, LiveConcert, LiveFormat, Platform},
+        spreadsheet::{escape_cell, get_price_columns, get_row_from_concert, get_table_from_concerts, Delimiter, COLUMNS},
+    };
+    
+    
+    
+
+    fn concert() -> LiveConcert {
+        ConcertBuilder::new()
+            .uid("first@test")
+            .title("First Live, \"Encore\"")
+            .format(LiveFormat::Both)
+            .price(JpyPrice::MultiTier(3500))
+            .platform(Platform::Spwn)
+            .description("Line one\nLine two")
+            .official("https://example.com/first")
+            .build()
+    }
+
+    #[test]
+    fn test_price_columns() {
+        assert_eq!(get_price_columns(&JpyPrice::Tbd), ("Tbd", None));
+        assert_eq!(get_price_columns(&JpyPrice::Free), ("Free", Some(0)));
+        assert_eq!(get_price_columns(&JpyPrice::Fixed(4000)), ("Fixed", Some(4000)));
+    }
+
+    #[test]
+    fn test_row_has_utc_and_japan_times() {
+        let row = get_row_from_concert(&concert());
+
+        assert_eq!(row.len(), COLUMNS.len());
+        assert_eq!(&row[2..11], ["Upcoming", "Spwn", "Both", "MultiTier", "3500", "2030-01-01 10:00:00", "2030-01-01 19:00:00", "", ""]);
+    }
+
+    #[test]
+    fn test_cells_are_escaped() {
+        assert_eq!(escape_cell("First Live, \"Encore\"", Delimiter::Comma), "\"First Live, \"\"Encore\"\"\"");
+        assert_eq!(escape_cell("Line one\nLine two", Delimiter::Tab), "Line one Line two");
+        assert_eq!(escape_cell("=HYPERLINK(\"x\")", Delimiter::Tab), "'=HYPERLINK(\"x\")");
+        assert_eq!(escape_cell("plain", Delimiter::Comma), "plain");
+    }
+
+    #[test]
+    fn test_table() {
+        let csv = get_table_from_concerts(&[concert()], Delimiter::Comma);
+        let lines: Vec<&str> = csv.trim_start_matches('\u{feff}').split_terminator("\r\n").collect();
+
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert!(lines[1].starts_with("00000000-0000-0000-0000-000000000001,\"First Live, \"\"Encore\"\"\",Upcoming,"));
+        // the description keeps its line break inside quotes
+        assert!(lines[1].ends_with(",\"Line one\nLine two\",first@test"));
+        assert_eq!(lines.len(), 2);
+    }
+}
@@ -0,0 +1,331 @@
+use super::changes::ChangeLog;
+use super::export::{get_main_link_from_concert, get_tag_from_platform, EXPORT_CALENDAR_NAME};
+use super::models::{JpyPrice, LiveConcert, LiveFormat};
+use chrono::{DateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use rocket::serde::Serialize;
+use schemars::JsonSchema;
+use std::fmt::Write;
+use url::Url;
+
+const HOLOCAL_URL: &str = "https://github.com/Skk-tj/HoloCal";
+const FEED_DESCRIPTION: &str = "Upcoming hololive concerts and live events";
+const ATOM_FEED_ID: &str = "tag:holo.wtf,2023:concerts";
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+pub fn get_price_text(price: &JpyPrice) -> String {
+    match price {
+        JpyPrice::Tbd => String::from("TBD"),
+        JpyPrice::Free => String::from("Free"),
+        JpyPrice::Fixed(yen) => format!("¥{}", yen),
+        JpyPrice::MultiTier(yen) => format!("from ¥{}", yen),
+    }
+}
+
+pub fn get_format_text(format: &LiveFormat) -> &'static str {
+    match format {
+        LiveFormat::Online => "Online",
+        LiveFormat::Irl => "In person",
+        LiveFormat::Both => "Online and in person",
+    }
+}
+
+/// Plain text body of a feed item: when and where the concert is, then the upstream description.
+pub fn get_text_from_concert(concert: &LiveConcert, tz: Tz) -> String {
+    let start = concert.start_time.with_timezone(&tz);
+    let starts = if concert.is_all_day {
+        format!("{} (all day)", start.format("%Y-%m-%d"))
+    } else {
+        format!("{} ({})", start.format("%Y-%m-%d %H:%M %Z"), concert.start_time.to_rfc3339_opts(SecondsFormat::Secs, true))
+    };
+
+    let mut text = format!(
+        "Starts: {}\nPlatform: {}\nFormat: {}\nPrice: {}",
+        starts,
+        get_tag_from_platform(&concert.platform),
+        get_format_text(&concert.format),
+        get_price_text(&concert.jpy_price)
+    );
+    if !concert.description.is_empty() {
+        text.push_str("\n\n");
+        text.push_str(&concert.description);
+    }
+    text
+}
+
+/// Media type of an image by its file extension, JPEG when there is nothing to go by.
+fn get_image_type_from_url(url: &Url) -> &'static str {
+    let path = url.path().to_lowercase();
+    if path.ends_with(".png") {
+        "image/png"
+    } else if path.ends_with(".gif") {
+        "image/gif"
+    } else if path.ends_with(".webp") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// Escapes `text` for XML 1.0, dropping the control characters it cannot carry at all, not
+/// even as character references.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {},
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A concert with the dates a feed reader sees it under, taken from the change log so items
+/// only look updated when their content actually changed.
+struct FeedEntry<'a> {
+    concert: &'a LiveConcert,
+    published: DateTime<Utc>,
+    updated: DateTime<Utc>,
+    text: String,
+}
+
+fn get_entries<'a>(concerts: &'a [LiveConcert], changes: &ChangeLog, fetched_at: DateTime<Utc>, tz: Tz) -> Vec<FeedEntry<'a>> {
+    concerts
+        .iter()
+        .map(|concert| {
+            let (published, updated) = changes.get_dates(&concert.id).unwrap_or((fetched_at, fetched_at));
+            FeedEntry { concert, published, updated, text: get_text_from_concert(concert, tz) }
+        })
+        .collect()
+}
+
+fn get_feed_updated(entries: &[FeedEntry], fetched_at: DateTime<Utc>) -> DateTime<Utc> {
+    entries.iter().map(|e| e.updated).max().unwrap_or(fetched_at)
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn get_atom_from_concerts(concerts: &[LiveConcert], changes: &ChangeLog, fetched_at: DateTime<Utc>, tz: Tz) -> String {
+    let entries = get_entries(concerts, changes, fetched_at, tz);
+    let mut atom = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(atom, "  <id>{}</id>", ATOM_FEED_ID);
+    let _ = writeln!(atom, "  <title>{}</title>", escape_xml(EXPORT_CALENDAR_NAME));
+    let _ = writeln!(atom, "  <subtitle>{}</subtitle>", escape_xml(FEED_DESCRIPTION));
+    let _ = writeln!(atom, "  <link rel=\"alternate\" href=\"{}\"/>", HOLOCAL_URL);
+    let _ = writeln!(atom, "  <updated>{}</updated>", rfc3339(get_feed_updated(&entries, fetched_at)));
+    let _ = writeln!(atom, "  <author><name>holo.wtf</name></author>");
+
+    for entry in entries.iter() {
+        let concert = entry.concert;
+        atom.push_str("  <entry>\n");
+        let _ = writeln!(atom, "    <id>urn:uuid:{}</id>", concert.id);
+        let _ = writeln!(atom, "    <title>{}</title>", escape_xml(&concert.title));
+        let _ = writeln!(atom, "    <published>{}</published>", rfc3339(entry.published));
+        let _ = writeln!(atom, "    <updated>{}</updated>", rfc3339(entry.updated));
+        if let Some(link) = &concert.official_link {
+            let _ = writeln!(atom, "    <link rel=\"alternate\" href=\"{}\"/>", escape_xml(link.as_str()));
+        }
+        if let Some(link) = &concert.ticket_link {
+            let _ = writeln!(atom, "    <link rel=\"related\" title=\"Tickets\" href=\"{}\"/>", escape_xml(link.as_str()));
+        }
+        if let Some(image) = &concert.image_url {
+            let _ = writeln!(atom, "    <link rel=\"enclosure\" type=\"{}\" href=\"{}\"/>", get_image_type_from_url(image), escape_xml(image.as_str()));
+        }
+        let _ = writeln!(atom, "    <category term=\"{}\"/>", escape_xml(get_tag_from_platform(&concert.platform)));
+        let _ = writeln!(atom, "    <content type=\"text\">{}</content>", escape_xml(&entry.text));
+        atom.push_str("  </entry>\n");
+    }
+
+    atom.push_str("</feed>\n");
+    atom
+}
+
+pub fn get_rss_from_concerts(concerts: &[LiveConcert], changes: &ChangeLog, fetched_at: DateTime<Utc>, tz: Tz) -> String {
+    let entries = get_entries(concerts, changes, fetched_at, tz);
+    let mut rss = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\">\n<channel>\n");
+    let _ = writeln!(rss, "  <title>{}</title>", escape_xml(EXPORT_CALENDAR_NAME));
+    let _ = writeln!(rss, "  <link>{}</link>", HOLOCAL_URL);
+    let _ = writeln!(rss, "  <description>{}</description>", escape_xml(FEED_DESCRIPTION));
+    let _ = writeln!(rss, "  <lastBuildDate>{}</lastBuildDate>", get_feed_updated(&entries, fetched_at).to_rfc2822());
+
+    for entry in entries.iter() {
+        let concert = entry.concert;
+        rss.push_str("  <item>\n");
+        let _ = writeln!(rss, "    <guid isPermaLink=\"false\">urn:uuid:{}</guid>", concert.id);
+        let _ = writeln!(rss, "    <title>{}</title>", escape_xml(&concert.title));
+        if let Some(link) = concert.official_link.as_ref().or(concert.ticket_link.as_ref()) {
+            let _ = writeln!(rss, "    <link>{}</link>", escape_xml(link.as_str()));
+        }
+        let _ = writeln!(rss, "    <pubDate>{}</pubDate>", entry.published.to_rfc2822());
+        if let Some(image) = &concert.image_url {
+            // the size is not known without downloading the image, 0 is what readers expect then
+            let _ = writeln!(rss, "    <enclosure url=\"{}\" length=\"0\" type=\"{}\"/>", escape_xml(image.as_str()), get_image_type_from_url(image));
+        }
+        let _ = writeln!(rss, "    <category>{}</category>", escape_xml(get_tag_from_platform(&concert.platform)));
+        let _ = writeln!(rss, "    <description>{}</description>", escape_xml(&entry.text));
+        rss.push_str("  </item>\n");
+    }
+
+    rss.push_str("</channel>\n</rss>\n");
+    rss
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct JsonFeed {
+    pub version: &'static str,
+    pub title: &'static str,
+    pub home_page_url: &'static str,
+    pub description: &'static str,
+    pub items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct JsonFeedItem {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_url: Option<Url>,
+    pub title: String,
+    pub content_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<Url>,
+    pub date_published: String,
+    pub date_modified: String,
+    pub tags: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct JsonFeedAttachment {
+    pub url: Url,
+    pub mime_type: &'static str,
+}
+
+pub fn get_json_feed_from_concerts(concerts: &[LiveConcert], changes: &ChangeLog, fetched_at: DateTime<Utc>, tz: Tz) -> JsonFeed {
+    let items = get_entries(concerts, changes, fetched_at, tz)
+        .into_iter()
+        .map(|entry| {
+            let concert = entry.concert;
+            JsonFeedItem {
+                id: concert.id.to_string(),
+                url: get_main_link_from_concert(concert).cloned(),
+                external_url: concert.ticket_link.clone(),
+                title: concert.title.clone(),
+                content_text: entry.text,
+                image: concert.image_url.clone(),
+                date_published: rfc3339(entry.published),
+                date_modified: rfc3339(entry.updated),
+                tags: vec![get_tag_from_platform(&concert.platform), get_format_text(&concert.format)],
+                attachments: concert.image_url
+                    .iter()
+                    .map(|image| JsonFeedAttachment { url: image.clone(), mime_type: get_image_type_from_url(image) })
+                    .collect()
+            }
+        })
+        .collect();
+
+    JsonFeed {
+        version: JSON_FEED_VERSION,
+        title: EXPORT_CALENDAR_NAME,
+        home_page_url: HOLOCAL_URL,
+        description: FEED_DESCRIPTION,
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        changes::ChangeLog,
+        models::{ConcertBuilder, JpyPrice, LiveConcert, Platform},
+        syndication::{escape_xml, get_atom_from_concerts, get_json_feed_from_concerts, get_rss_from_concerts, get_text_from_concert},
+    };
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use chrono_tz::Asia::Tokyo;
+    
+    
+
+    fn concert() -> LiveConcert {
+        ConcertBuilder::new()
+            .uid("first@test")
+            .title("Fish & Chips <Live>")
+            .price(JpyPrice::MultiTier(3500))
+            .platform(Platform::Zan)
+            .description("Z-aN link: https://www.zan-live.com/live/detail/10001")
+            .starts(Utc.with_ymd_and_hms(2030, 2, 1, 10, 0, 0).unwrap())
+            .image("https://example.com/poster.png")
+            .tickets("https://example.com/tickets?a=1&b=2")
+            .official("https://example.com/first")
+            .build()
+    }
+
+    fn fetched_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn changes() -> ChangeLog {
+        let mut changes = ChangeLog::new(fetched_at());
+        changes.update(&[concert()], fetched_at() - Duration::days(1));
+        changes
+    }
+
+    #[test]
+    fn test_text_has_start_in_japan_time() {
+        let text = get_text_from_concert(&concert(), Tokyo);
+        assert!(text.starts_with("Starts: 2030-02-01 19:00 JST (2030-02-01T10:00:00Z)\nPlatform: Z-aN\nFormat: Online\nPrice: from ¥3500\n\n"));
+    }
+
+    #[test]
+    fn test_escape_xml_drops_control_characters() {
+        assert_eq!(escape_xml("Live\u{0}\u{8} <1>\u{b}\u{c}\u{1f}"), "Live &lt;1&gt;");
+        assert_eq!(escape_xml("line\tone\r\nline two"), "line\tone\r\nline two");
+    }
+
+    #[test]
+    fn test_atom() {
+        let atom = get_atom_from_concerts(&[concert()], &changes(), fetched_at(), Tokyo);
+
+        assert!(atom.contains("<title>Fish &amp; Chips &lt;Live&gt;</title>"));
+        assert!(atom.contains("<id>urn:uuid:00000000-0000-0000-0000-000000000001</id>"));
+        assert!(atom.contains("<updated>2028-12-31T00:00:00Z</updated>"));
+        assert!(atom.contains("<link rel=\"alternate\" href=\"https://example.com/first\"/>"));
+        assert!(atom.contains("<link rel=\"related\" title=\"Tickets\" href=\"https://example.com/tickets?a=1&amp;b=2\"/>"));
+        assert!(atom.contains("<link rel=\"enclosure\" type=\"image/png\" href=\"https://example.com/poster.png\"/>"));
+    }
+
+    #[test]
+    fn test_rss() {
+        let rss = get_rss_from_concerts(&[concert()], &changes(), fetched_at(), Tokyo);
+
+        assert!(rss.contains("<guid isPermaLink=\"false\">urn:uuid:00000000-0000-0000-0000-000000000001</guid>"));
+        assert!(rss.contains("<link>https://example.com/first</link>"));
+        assert!(rss.contains("<pubDate>Sun, 31 Dec 2028 00:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<enclosure url=\"https://example.com/poster.png\" length=\"0\" type=\"image/png\"/>"));
+    }
+
+    #[test]
+    fn test_json_feed() {
+        let feed = get_json_feed_from_concerts(&[concert()], &ChangeLog::default(), fetched_at(), Tokyo);
+        let json = rocket::serde::json::to_value(&feed).unwrap();
+
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["items"][0]["url"], "https://example.com/first");
+        assert_eq!(json["items"][0]["external_url"], "https://example.com/tickets?a=1&b=2");
+        assert_eq!(json["items"][0]["attachments"][0]["mime_type"], "image/png");
+        // not in the change log, so dated by the fetch
+        assert_eq!(json["items"][0]["date_published"], "2029-01-01T00:00:00Z");
+    }
+}
@@ -0,0 +1,335 @@
+use chrono::{offset::LocalResult, DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc};
+use super::parse_error::ParseError;
+use chrono_tz::Tz;
+use icalendar::{Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime};
+use log::warn;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Windows time zone names seen in Outlook and Exchange exports, mapped to the IANA zone CLDR
+/// lists as their primary location.
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("Argentina Standard Time", "America/Buenos_Aires"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("FLE Standard Time", "Europe/Kiev"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("India Standard Time", "Asia/Calcutta"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("W. Australia Standard Time", "Australia/Perth"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("Cen. Australia Standard Time", "Australia/Adelaide"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+];
+
+/// A zone a TZID was resolved to, either a real IANA zone or the fixed offset of a
+/// `VTIMEZONE` that never observes daylight saving time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResolvedZone {
+    Iana(Tz),
+    Fixed(FixedOffset),
+}
+
+/// Turns the date and time values of a feed into UTC instants.
+///
+/// Floating times and all-day dates carry no zone of their own, so both are read in
+/// `default_tz`, which for the Teamup feed is Japan time. TZIDs are looked up as IANA names,
+/// then in the feed's own `VTIMEZONE` definitions, then as Windows zone names.
+#[derive(Debug, Clone)]
+pub struct TimeZoneResolver {
+    default_tz: Tz,
+    feed_zones: HashMap<String, ResolvedZone>,
+}
+
+impl TimeZoneResolver {
+    pub fn new(default_tz: Tz) -> Self {
+        Self { default_tz, feed_zones: HashMap::new() }
+    }
+
+    /// A copy of this resolver that also knows the `VTIMEZONE` blocks of `calendar`.
+    pub fn with_calendar_timezones(&self, calendar: &Calendar) -> Self {
+        let mut resolver = self.clone();
+
+        for component in calendar.components.iter() {
+            if let CalendarComponent::Other(other) = component {
+                if other.component_kind() != "VTIMEZONE" {
+                    continue;
+                }
+                let Some(tzid) = other.property_value("TZID") else {
+                    continue;
+                };
+
+                match get_zone_from_vtimezone(other) {
+                    Some(zone) => {
+                        resolver.feed_zones.insert(String::from(tzid), zone);
+                    },
+                    None => warn!("VTIMEZONE \"{}\" has no known location and observes daylight saving time, ignoring it", tzid)
+                }
+            }
+        }
+
+        resolver
+    }
+
+    pub fn resolve_tzid(&self, tzid: &str) -> Result<ResolvedZone, ParseError> {
+        let tzid = tzid.trim().trim_matches('"');
+
+        if let Ok(tz) = tzid.parse::<Tz>() {
+            return Ok(ResolvedZone::Iana(tz));
+        }
+
+        if let Some(zone) = self.feed_zones.get(tzid) {
+            return Ok(*zone);
+        }
+
+        if let Some((_, iana)) = WINDOWS_ZONES.iter().find(|(windows, _)| windows.eq_ignore_ascii_case(tzid)) {
+            return Ok(ResolvedZone::Iana(iana.parse().unwrap()));
+        }
+
+        get_iana_zone_from_path(tzid)
+            .map(ResolvedZone::Iana)
+            .ok_or(ParseError::bad_time(tzid))
+    }
+
+    pub fn resolve(&self, d: &DatePerhapsTime) -> Result<DateTime<Utc>, ParseError> {
+        match d {
+            DatePerhapsTime::Date(naive_date) => Ok(self.resolve_date(*naive_date)),
+            DatePerhapsTime::DateTime(date_time) => match date_time {
+                CalendarDateTime::Utc(utc) => Ok(*utc),
+                CalendarDateTime::Floating(naive) => Ok(resolve_local(*naive, self.default_tz)),
+                CalendarDateTime::WithTimezone { date_time, tzid } => match self.resolve_tzid(tzid)? {
+                    ResolvedZone::Iana(tz) => Ok(resolve_local(*date_time, tz)),
+                    ResolvedZone::Fixed(offset) => Ok(Utc.from_utc_datetime(&(*date_time - offset)))
+                }
+            }
+        }
+    }
+
+    /// Start of the given day in the default time zone.
+    pub fn resolve_date(&self, date: NaiveDate) -> DateTime<Utc> {
+        resolve_local(date.and_time(NaiveTime::MIN), self.default_tz)
+    }
+}
+
+/// Reads the zone of a `VTIMEZONE`, from its `X-LIC-LOCATION` or a TZID ending in an IANA name
+/// such as `/citadel.org/20190101_1/Asia/Tokyo`, or else from its single fixed offset.
+fn get_zone_from_vtimezone(vtimezone: &impl Component) -> Option<ResolvedZone> {
+    if let Some(tz) = vtimezone.property_value("X-LIC-LOCATION").and_then(|l| l.trim().parse::<Tz>().ok()) {
+        return Some(ResolvedZone::Iana(tz));
+    }
+
+    if let Some(tz) = vtimezone.property_value("TZID").and_then(get_iana_zone_from_path) {
+        return Some(ResolvedZone::Iana(tz));
+    }
+
+    let observances = vtimezone.components();
+    if observances.iter().any(|o| o.component_kind() == "DAYLIGHT") {
+        return None;
+    }
+
+    let mut offsets = observances.iter()
+        .filter(|o| o.component_kind() == "STANDARD")
+        .filter_map(|o| o.property_value("TZOFFSETTO"))
+        .map(get_offset_from_string);
+    match (offsets.next(), offsets.next()) {
+        (Some(Some(offset)), None) => Some(ResolvedZone::Fixed(offset)),
+        _ => None
+    }
+}
+
+/// Finds an IANA name at the end of a path-like TZID, trying the last three segments, then
+/// the last two, then the last one.
+fn get_iana_zone_from_path(tzid: &str) -> Option<Tz> {
+    let segments: Vec<&str> = tzid.split('/').filter(|s| !s.is_empty()).collect();
+
+    (1..=segments.len().min(3)).rev()
+        .map(|n| segments[segments.len() - n..].join("/"))
+        .find_map(|candidate| candidate.parse::<Tz>().ok())
+}
+
+/// Parses a UTC offset such as `+0900` or `-043000`.
+fn get_offset_from_string(offset: &str) -> Option<FixedOffset> {
+    let matcher = Regex::new(r"^([+-])(\d{2})(\d{2})(\d{2})?$").unwrap();
+    let matched = matcher.captures(offset.trim())?;
+
+    let seconds = matched[2].parse::<i32>().ok()? * 3600
+        + matched[3].parse::<i32>().ok()? * 60
+        + matched.get(4).map_or(Some(0), |s| s.as_str().parse::<i32>().ok())?;
+
+    if &matched[1] == "-" {
+        FixedOffset::west_opt(seconds)
+    } else {
+        FixedOffset::east_opt(seconds)
+    }
+}
+
+/// Converts a wall-clock time in `tz` to UTC following RFC 5545 section 3.3.5: a time repeated
+/// by a backward shift means its first occurrence, and a time skipped by a forward shift is read
+/// with the UTC offset from before the gap.
+pub fn resolve_local(naive: NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(t) => t.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        LocalResult::None => {
+            // no zone has a gap longer than a day, so a day earlier is safely before it
+            let offset_before_gap = tz.offset_from_utc_datetime(&(naive - Duration::days(1))).fix();
+            Utc.from_utc_datetime(&(naive - offset_before_gap))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{
+        parse_error::ParseError,
+        timezone::{ResolvedZone, TimeZoneResolver}
+    };
+    use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+    use chrono_tz::{Asia::Tokyo, Tz};
+    use icalendar::{parser::{read_calendar, unfold}, Calendar, CalendarDateTime, DatePerhapsTime};
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//test//test//EN\r
+BEGIN:VTIMEZONE\r
+TZID:Custom Seoul\r
+X-LIC-LOCATION:Asia/Seoul\r
+END:VTIMEZONE\r
+BEGIN:VTIMEZONE\r
+TZID:Made Up Standard Time\r
+BEGIN:STANDARD\r
+DTSTART:19700101T000000\r
+TZOFFSETFROM:+0530\r
+TZOFFSETTO:+0530\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VTIMEZONE\r
+TZID:Made Up Daylight Time\r
+BEGIN:STANDARD\r
+DTSTART:19701025T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+END:STANDARD\r
+BEGIN:DAYLIGHT\r
+DTSTART:19700329T020000\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+END:DAYLIGHT\r
+END:VTIMEZONE\r
+END:VCALENDAR\r
+";
+
+    fn resolver() -> TimeZoneResolver {
+        TimeZoneResolver::new(Tokyo)
+    }
+
+    #[test]
+    fn test_utc() {
+        let utc = Utc.with_ymd_and_hms(2026, 11, 1, 10, 0, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::Utc(utc));
+        assert_eq!(resolver().resolve(&d), Ok(utc));
+    }
+
+    #[test]
+    fn test_floating_uses_default_zone() {
+        let naive = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap().and_hms_opt(19, 0, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::Floating(naive));
+        assert_eq!(resolver().resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 11, 1, 10, 0, 0).unwrap()));
+
+        let in_london = TimeZoneResolver::new("Europe/London".parse::<Tz>().unwrap());
+        assert_eq!(in_london.resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 11, 1, 19, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_with_timezone() {
+        let naive = NaiveDate::from_ymd_opt(2026, 7, 1).unwrap().and_hms_opt(20, 0, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time: naive, tzid: String::from("America/New_York") });
+        assert_eq!(resolver().resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 7, 2, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_ambiguous_time_takes_first_occurrence() {
+        // 01:30 happens twice in New York when DST ends on 2026-11-01
+        let naive = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap().and_hms_opt(1, 30, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time: naive, tzid: String::from("America/New_York") });
+        assert_eq!(resolver().resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 11, 1, 5, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_nonexistent_time_uses_offset_before_gap() {
+        // 02:30 is skipped in New York when DST starts on 2026-03-08
+        let naive = NaiveDate::from_ymd_opt(2026, 3, 8).unwrap().and_hms_opt(2, 30, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time: naive, tzid: String::from("America/New_York") });
+        assert_eq!(resolver().resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 3, 8, 7, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_all_day_date_is_midnight_in_default_zone() {
+        let d = DatePerhapsTime::Date(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap());
+        assert_eq!(resolver().resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 10, 31, 15, 0, 0).unwrap()));
+    }
+
+    fn feed_resolver() -> TimeZoneResolver {
+        let calendar: Calendar = read_calendar(unfold(CALENDAR).as_str()).unwrap().into();
+        resolver().with_calendar_timezones(&calendar)
+    }
+
+    #[test]
+    fn test_windows_zone_name() {
+        assert_eq!(resolver().resolve_tzid("Tokyo Standard Time"), Ok(ResolvedZone::Iana(Tokyo)));
+    }
+
+    #[test]
+    fn test_path_like_tzid() {
+        assert_eq!(resolver().resolve_tzid("/citadel.org/20190101_1/Asia/Tokyo"), Ok(ResolvedZone::Iana(Tokyo)));
+        assert_eq!(resolver().resolve_tzid("/freeassociation.sourceforge.net/America/Argentina/Buenos_Aires"), Ok(ResolvedZone::Iana("America/Argentina/Buenos_Aires".parse().unwrap())));
+    }
+
+    #[test]
+    fn test_vtimezone_with_location() {
+        assert_eq!(feed_resolver().resolve_tzid("Custom Seoul"), Ok(ResolvedZone::Iana("Asia/Seoul".parse().unwrap())));
+    }
+
+    #[test]
+    fn test_vtimezone_with_fixed_offset() {
+        let naive = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time: naive, tzid: String::from("Made Up Standard Time") });
+
+        assert_eq!(feed_resolver().resolve_tzid("Made Up Standard Time"), Ok(ResolvedZone::Fixed(FixedOffset::east_opt(5 * 3600 + 1800).unwrap())));
+        assert_eq!(feed_resolver().resolve(&d), Ok(Utc.with_ymd_and_hms(2026, 11, 1, 6, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_unresolvable_tzid_is_an_error() {
+        let naive = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let d = DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time: naive, tzid: String::from("Made Up Daylight Time") });
+
+        assert_eq!(feed_resolver().resolve(&d), Err(ParseError::bad_time("Made Up Daylight Time")));
+    }
+}
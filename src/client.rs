@@ -0,0 +1,184 @@
+use crate::calendar::filter::{get_param_from_format, get_param_from_platform};
+use crate::calendar::models::{ConcertChanges, ConcertPage, LiveConcert, LiveFormat, Platform};
+use chrono::NaiveDate;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::fmt;
+use url::Url;
+use uuid::Uuid;
+
+/// Filters of the concert list, leaving everything at its default lists every upcoming concert.
+///
+/// Empty `platforms` and `formats` match any, `from` and `to` are days in the server's default
+/// timezone and both inclusive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConcertListFilter {
+    pub platforms: Vec<Platform>,
+    pub formats: Vec<LiveFormat>,
+    pub max_price: Option<i32>,
+    pub free: Option<bool>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl ConcertListFilter {
+    /// The filter as the query parameters of the list.
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![];
+        if !self.platforms.is_empty() {
+            query.push(("platform", self.platforms.iter().map(get_param_from_platform).collect::<Vec<_>>().join(",")));
+        }
+        if !self.formats.is_empty() {
+            query.push(("format", self.formats.iter().map(get_param_from_format).collect::<Vec<_>>().join(",")));
+        }
+        if let Some(max_price) = self.max_price {
+            query.push(("max_price", max_price.to_string()));
+        }
+        if let Some(free) = self.free {
+            query.push(("free", free.to_string()));
+        }
+        if let Some(from) = self.from {
+            query.push(("from", from.format("%Y-%m-%d").to_string()));
+        }
+        if let Some(to) = self.to {
+            query.push(("to", to.format("%Y-%m-%d").to_string()));
+        }
+        query
+    }
+}
+
+/// Body the API answers every error with.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ApiErrorBody {
+    pub code: String,
+    pub message: String,
+    /// Seconds after which trying again may succeed, `None` when it will not help.
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent or its body could not be read as what was asked for.
+    Request(reqwest::Error),
+    /// The API answered with one of its errors.
+    Api { status: u16, body: ApiErrorBody },
+    /// Something answered with an error that did not come from the API, e.g. a proxy in between.
+    Unexpected { status: u16 },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Request(e) => write!(f, "request failed, {}", e),
+            ClientError::Api { status, body } => write!(f, "API answered {} {}, {}", status, body.code, body.message),
+            ClientError::Unexpected { status } => write!(f, "answered {} without an API error", status),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Request(e)
+    }
+}
+
+/// Typed client of the `/v1` API.
+#[derive(Debug, Clone)]
+pub struct HoloWtfClient {
+    http: reqwest::Client,
+    base_url: Url,
+}
+
+impl HoloWtfClient {
+    /// A client of the API served at `base_url`, e.g. `https://example.com/`.
+    pub fn new(base_url: Url) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Same as [`HoloWtfClient::new`], sending requests through `http` so timeouts, proxies and
+    /// the like can be set up by the caller.
+    pub fn with_http_client(mut base_url: Url, http: reqwest::Client) -> Self {
+        // without the slash, joining would replace the last segment of the path
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Self { http, base_url }
+    }
+
+    fn endpoint(&self, path: &str) -> Url {
+        self.base_url.join(path).expect("API paths are valid relative URLs")
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url, query: &[(&str, String)]) -> Result<T, ClientError> {
+        let response = self.http.get(url).query(query).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json::<T>().await?);
+        }
+
+        match response.json::<ApiErrorBody>().await {
+            Ok(body) => Err(ClientError::Api { status: status.as_u16(), body }),
+            Err(_) => Err(ClientError::Unexpected { status: status.as_u16() })
+        }
+    }
+
+    /// Upcoming and running concerts that pass `filter`, in the order of the calendar feeds
+    /// rather than by start time.
+    pub async fn list_concerts(&self, filter: &ConcertListFilter) -> Result<Vec<LiveConcert>, ClientError> {
+        self.get(self.endpoint("v1"), &filter.to_query()).await
+    }
+
+    /// A page of the concerts that have ended, newest first. Pages start at 1.
+    pub async fn list_past_concerts(&self, page: usize, per_page: usize) -> Result<ConcertPage, ClientError> {
+        let query = [("page", page.to_string()), ("per_page", per_page.to_string())];
+        self.get(self.endpoint("v1/concerts/past"), &query).await
+    }
+
+    /// A single concert, past ones included.
+    pub async fn get_concert(&self, id: Uuid) -> Result<LiveConcert, ClientError> {
+        self.get(self.endpoint(&format!("v1/concerts/{}", id)), &[]).await
+    }
+
+    /// What changed since `since`, the cursor of an earlier answer, or everything without one.
+    pub async fn get_changes(&self, since: Option<&str>) -> Result<ConcertChanges, ClientError> {
+        let query: Vec<(&str, String)> = since.map(|s| ("since", s.to_string())).into_iter().collect();
+        self.get(self.endpoint("v1/concerts/changes"), &query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::models::{LiveFormat, Platform};
+    use crate::client::{ConcertListFilter, HoloWtfClient};
+    use chrono::NaiveDate;
+    use url::Url;
+
+    #[test]
+    fn test_filter_query() {
+        assert!(ConcertListFilter::default().to_query().is_empty());
+
+        let filter = ConcertListFilter {
+            platforms: vec![Platform::Spwn, Platform::Zan],
+            formats: vec![LiveFormat::Online],
+            free: Some(true),
+            from: NaiveDate::from_ymd_opt(2030, 1, 1),
+            ..Default::default()
+        };
+        assert_eq!(filter.to_query(), vec![
+            ("platform", String::from("spwn,zan")),
+            ("format", String::from("online")),
+            ("free", String::from("true")),
+            ("from", String::from("2030-01-01")),
+        ]);
+    }
+
+    #[test]
+    fn test_endpoints_keep_the_base_path() {
+        let client = HoloWtfClient::new(Url::parse("https://example.com/api").unwrap());
+        assert_eq!(client.endpoint("v1/concerts/past").as_str(), "https://example.com/api/v1/concerts/past");
+
+        let client = HoloWtfClient::new(Url::parse("https://example.com").unwrap());
+        assert_eq!(client.endpoint("v1").as_str(), "https://example.com/v1");
+    }
+}
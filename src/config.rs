@@ -0,0 +1,36 @@
+use holo_wtf_api::calendar::{
+    feed::{default_feeds, Feed},
+    source::FetchConfig
+};
+use chrono_tz::{Asia::Tokyo, Tz};
+use rocket::serde::Deserialize;
+use std::path::PathBuf;
+
+fn default_refresh_interval() -> u64 {
+    300
+}
+
+fn default_timezone() -> Tz {
+    Tokyo
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AppConfig {
+    /// Seconds between two background fetches of the upstream calendar.
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval: u64,
+    /// IANA zone used for floating times, all-day dates and date query parameters.
+    #[serde(default = "default_timezone")]
+    pub default_timezone: Tz,
+    /// Upstream feeds merged into the concert list, the Teamup calendar when not set.
+    #[serde(default = "default_feeds")]
+    pub feeds: Vec<Feed>,
+    /// Timeouts, retries and size limits of upstream fetches.
+    #[serde(default)]
+    pub fetch: FetchConfig,
+    /// Directory the last good calendar of every feed is saved to and loaded from on startup,
+    /// nothing is saved when not set.
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
+}
@@ -0,0 +1,82 @@
+use rocket::{
+    http::{Header, Status},
+    request::Request,
+    response::{self, Responder, Response},
+    serde::{json::Json, Serialize}
+};
+use schemars::JsonSchema;
+
+/// Seconds a client is asked to wait while the first fetch is still running.
+const NOT_LOADED_RETRY_AFTER: u64 = 10;
+
+/// Body of every error response, including the ones from the catchers.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    /// Seconds after which trying again may succeed, also sent as `Retry-After`.
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub body: ErrorBody,
+}
+
+impl ApiError {
+    pub fn with_retry_after(mut self, seconds: u64) -> Self {
+        self.body.retry_after = Some(seconds);
+        self
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let retry_after = self.body.retry_after;
+        let mut response = Response::build_from(Json(self.body).respond_to(request)?);
+        response.status(self.status);
+        if let Some(seconds) = retry_after {
+            response.header(Header::new("Retry-After", seconds.to_string()));
+        }
+        response.ok()
+    }
+}
+
+pub fn api_error(status: Status, code: &'static str, message: impl Into<String>) -> ApiError {
+    ApiError { status, body: ErrorBody { code, message: message.into(), retry_after: None } }
+}
+
+pub fn not_loaded() -> ApiError {
+    api_error(Status::ServiceUnavailable, "not_loaded", "calendar is not loaded yet")
+        .with_retry_after(NOT_LOADED_RETRY_AFTER)
+}
+
+/// The calendar could not be fetched from upstream and nothing was loaded before, the details
+/// stay in the log.
+pub fn upstream_unavailable(retry_after: u64) -> ApiError {
+    api_error(Status::BadGateway, "upstream_unavailable", "the upstream calendar could not be fetched")
+        .with_retry_after(retry_after)
+}
+
+/// Upstream answered, but with something that is not a calendar we can read.
+pub fn calendar_unparseable(retry_after: u64) -> ApiError {
+    api_error(Status::InternalServerError, "calendar_unparseable", "the upstream calendar could not be parsed")
+        .with_retry_after(retry_after)
+}
+
+#[catch(404)]
+pub fn not_found(request: &Request<'_>) -> ApiError {
+    api_error(Status::NotFound, "not_found", format!("nothing is served at {}", request.uri().path()))
+}
+
+#[catch(422)]
+pub fn unprocessable_entity(request: &Request<'_>) -> ApiError {
+    api_error(Status::UnprocessableEntity, "invalid_request", format!("{} could not be understood", request.uri().path()))
+}
+
+#[catch(500)]
+pub fn internal_error() -> ApiError {
+    api_error(Status::InternalServerError, "internal_error", "something went wrong on our side")
+}
@@ -0,0 +1,11 @@
+//! Models and parser of the hololive concert calendar behind holo.wtf, the Swift mirrors of the
+//! models, and with the `client` feature a typed client of its API. The API server itself is
+//! the binary of this crate and needs the `server` feature.
+
+#[cfg(feature = "server")]
+#[macro_use] extern crate rocket;
+
+pub mod calendar;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod swift;
@@ -1,78 +1,584 @@
-use icalendar::{
-    parser::{read_calendar, unfold},
-    {Calendar, Component, Event},
-    DatePerhapsTime::{Date, DateTime},
-    CalendarDateTime::{Floating, Utc, WithTimezone}
-};
 use rocket::{
-    serde::json::Json,
-    response::status::NotFound
+    fairing::AdHoc,
+    http::{ContentType, Status},
+    serde::json::{Json, Value},
+    Build,
+    Rocket,
+    Route,
+    State
 };
 use chrono::offset;
-use chrono_tz::{
-    Tz,
-    Asia::Tokyo
-};
-use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
 
-mod calendar;
+mod config;
+mod error;
+mod openapi;
+mod response;
 
-use crate::calendar::{
-    calendar_parser::get_concert_from_event,
-    calendar_parser::get_concert_calendar_in_string,
-    models::LiveConcert
+use holo_wtf_api::calendar::{
+    archive::{get_past_concerts, ArchiveQuery},
+    export::{get_calendar_from_concerts, get_reminders_from_param},
+    filter::{ConcertFilter, ConcertQuery},
+    models::{ConcertChanges, ConcertPage, Diagnostics, LiveConcert, LiveStatus},
+    persist::SnapshotDir,
+    snapshot::{CalendarRefresher, CalendarSnapshot, RefreshFailure, SnapshotStore},
+    spreadsheet::{get_table_from_concerts, Delimiter},
+    syndication::{get_atom_from_concerts, get_json_feed_from_concerts, get_rss_from_concerts, JsonFeed},
+    timezone::TimeZoneResolver
+};
+use crate::config::AppConfig;
+use crate::error::{
+    api_error,
+    calendar_unparseable,
+    internal_error,
+    not_found,
+    not_loaded,
+    unprocessable_entity,
+    upstream_unavailable,
+    ApiError
 };
+use crate::openapi::{get_openapi_from_rocket, OpenApiDocument, API_BASE};
+use crate::response::{SnapshotDocument, SnapshotJson};
 
 #[macro_use] extern crate rocket;
 
-#[get("/")]
-async fn index() -> Result<Json<Vec<LiveConcert>>, NotFound<String>> {
-    let calendar_string = match get_concert_calendar_in_string().await {
-        Ok(s) => s,
-        Err(e) => return Err(NotFound(e.to_string()))
-    };
-
-    match read_calendar(unfold(calendar_string.as_str()).as_str()) {
-        Ok(c) => {
-            let calendar: Calendar = c.into();
-            let all_lives = calendar.components
-                .iter()
-                .filter_map(|c| c.as_event())
-                .filter(|e| is_future_event(e))
-                .filter_map(|e| get_concert_from_event(e)
-                    .map_err(|err| warn!("getting concert from event failed, the error is {}, the event is {:?}", err, e))
-                    .ok())
-                .collect();
-
-            Ok(Json(all_lives))
-        },
-        Err(e) => Err(NotFound(e))
-    }
-}
-
-fn is_future_event(event: &Event) -> bool {
-    let start = event.get_start();
-
-    match start {
-        Some(d) => match d {
-            Date(naive_date) => naive_date > offset::Utc::now().date_naive(),
-            DateTime(date_time) => match date_time {
-                Utc(utc) => utc > offset::Utc::now(),
-                Floating(naive) => naive.and_local_timezone(Tokyo).unwrap() > offset::Utc::now(),
-                WithTimezone { date_time, tzid } => {
-                    let tz: Tz = tzid.parse().unwrap();
-                    match date_time.and_local_timezone(tz) {
-                        offset::LocalResult::Single(t) => t > offset::Utc::now(),
-                        _ => false
-                    }
-                }
-            }
-        },
-        _ => false
+/// The latest snapshot, or why there is none yet. Failed refreshes are retried after
+/// `refresh_interval`, so that is when clients are told to come back.
+fn current_snapshot(store: &SnapshotStore, config: &AppConfig) -> Result<Arc<CalendarSnapshot>, ApiError> {
+    match (store.load(), store.failure()) {
+        (Some(snapshot), _) => Ok(snapshot),
+        (None, Some(RefreshFailure::Fetch)) => Err(upstream_unavailable(config.refresh_interval)),
+        (None, Some(RefreshFailure::Parse)) => Err(calendar_unparseable(config.refresh_interval)),
+        (None, None) => Err(not_loaded())
     }
 }
 
+/// The filter of `query`, checked before the snapshot so a bad query is a 400 even while
+/// nothing is loaded yet.
+fn get_filter_from_query(query: &ConcertQuery<'_>, config: &AppConfig) -> Result<ConcertFilter, ApiError> {
+    query.to_filter(config.default_timezone)
+        .map_err(|e| api_error(Status::BadRequest, "invalid_query", e))
+}
+
+/// Concerts that have not ended yet and pass `filter`, with their current status.
+fn get_upcoming_concerts(filter: &ConcertFilter, snapshot: &CalendarSnapshot) -> Vec<LiveConcert> {
+    let now = offset::Utc::now();
+
+    snapshot.concerts
+        .iter()
+        .filter(|c| c.status_at(now) != LiveStatus::Ended)
+        .filter(|c| filter.matches(c))
+        .map(|c| c.clone().with_status_at(now))
+        .collect()
+}
+
+#[get("/?<query..>")]
+fn index(query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotJson<Vec<LiveConcert>>, ApiError> {
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let upcoming_lives = get_upcoming_concerts(&filter, &snapshot);
+
+    Ok(SnapshotJson::new(upcoming_lives, &snapshot))
+}
+
+/// The same concerts as the list, as an iCalendar feed to subscribe to. `remind` lists minutes
+/// before the start to add alarms at, e.g. `60,1440`.
+#[get("/concerts.ics?<remind>&<query..>")]
+fn concerts_ics(remind: Option<&str>, query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotDocument, ApiError> {
+    let reminders = remind.map(get_reminders_from_param).transpose()
+        .map_err(|e| api_error(Status::BadRequest, "invalid_query", e))?
+        .unwrap_or_default();
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let concerts = get_upcoming_concerts(&filter, &snapshot);
+    let calendar = get_calendar_from_concerts(&concerts, config.default_timezone, snapshot.fetched_at, &reminders);
+
+    Ok(SnapshotDocument::new(calendar.to_string(), ContentType::Calendar, &snapshot))
+}
+
+/// The same concerts as the list, flattened into one row each for spreadsheets.
+#[get("/concerts.csv?<query..>")]
+fn concerts_csv(query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotDocument, ApiError> {
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let concerts = get_upcoming_concerts(&filter, &snapshot);
+
+    Ok(SnapshotDocument::new(get_table_from_concerts(&concerts, Delimiter::Comma), ContentType::CSV, &snapshot))
+}
+
+#[get("/concerts.tsv?<query..>")]
+fn concerts_tsv(query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotDocument, ApiError> {
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let concerts = get_upcoming_concerts(&filter, &snapshot);
+    let content_type = ContentType::new("text", "tab-separated-values").with_params(("charset", "utf-8"));
+
+    Ok(SnapshotDocument::new(get_table_from_concerts(&concerts, Delimiter::Tab), content_type, &snapshot))
+}
+
+#[get("/concerts.atom?<query..>")]
+fn concerts_atom(query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotDocument, ApiError> {
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let concerts = get_upcoming_concerts(&filter, &snapshot);
+    let atom = get_atom_from_concerts(&concerts, &snapshot.changes, snapshot.fetched_at, config.default_timezone);
+
+    Ok(SnapshotDocument::new(atom, ContentType::new("application", "atom+xml"), &snapshot))
+}
+
+#[get("/concerts.rss?<query..>")]
+fn concerts_rss(query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotDocument, ApiError> {
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let concerts = get_upcoming_concerts(&filter, &snapshot);
+    let rss = get_rss_from_concerts(&concerts, &snapshot.changes, snapshot.fetched_at, config.default_timezone);
+
+    Ok(SnapshotDocument::new(rss, ContentType::new("application", "rss+xml"), &snapshot))
+}
+
+/// The list as a [JSON Feed 1.1](https://jsonfeed.org/version/1.1).
+#[get("/concerts.json?<query..>")]
+fn concerts_json_feed(query: ConcertQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotJson<JsonFeed>, ApiError> {
+    let filter = get_filter_from_query(&query, config)?;
+    let snapshot = current_snapshot(store, config)?;
+    let concerts = get_upcoming_concerts(&filter, &snapshot);
+    let feed = get_json_feed_from_concerts(&concerts, &snapshot.changes, snapshot.fetched_at, config.default_timezone);
+
+    Ok(SnapshotJson::new(feed, &snapshot).with_content_type(ContentType::new("application", "feed+json")))
+}
+
+#[get("/concerts/past?<query..>")]
+fn past_concerts(query: ArchiveQuery<'_>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotJson<ConcertPage>, ApiError> {
+    let filter = query.to_archive_filter(config.default_timezone)
+        .map_err(|e| api_error(Status::BadRequest, "invalid_query", e))?;
+    let snapshot = current_snapshot(store, config)?;
+
+    Ok(SnapshotJson::new(get_past_concerts(&snapshot.concerts, &filter, offset::Utc::now()), &snapshot))
+}
+
+/// Concerts added, updated and removed since `since`, a cursor from an earlier response.
+/// Covers every concert in the calendar, past ones included.
+#[get("/concerts/changes?<since>")]
+fn concert_changes(since: Option<&str>, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotJson<ConcertChanges>, ApiError> {
+    let snapshot = current_snapshot(store, config)?;
+    let now = offset::Utc::now();
+    let concerts: Vec<LiveConcert> = snapshot.concerts
+        .iter()
+        .map(|c| c.clone().with_status_at(now))
+        .collect();
+
+    let changes = snapshot.changes.changes_since(since, &concerts)
+        .map_err(|e| api_error(Status::BadRequest, "invalid_query", e))?;
+    Ok(SnapshotJson::new(changes, &snapshot))
+}
+
+#[get("/concerts/<id>")]
+fn concert(id: Uuid, store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotJson<LiveConcert>, ApiError> {
+    let snapshot = current_snapshot(store, config)?;
+
+    snapshot.concerts
+        .iter()
+        .find(|c| c.id == id)
+        .map(|c| SnapshotJson::new(c.clone().with_status_at(offset::Utc::now()), &snapshot))
+        .ok_or_else(|| api_error(Status::NotFound, "concert_not_found", format!("no concert with id {}", id)))
+}
+
+/// Events the parser rejected in the latest snapshot, for whoever maintains the calendar.
+#[get("/diagnostics")]
+fn diagnostics(store: &State<SnapshotStore>, config: &State<AppConfig>) -> Result<SnapshotJson<Diagnostics>, ApiError> {
+    let snapshot = current_snapshot(store, config)?;
+    Ok(SnapshotJson::new(snapshot.diagnostics(), &snapshot).modified_at(snapshot.fetched_at))
+}
+
+/// OpenAPI 3 document of everything under `/v1`.
+#[get("/openapi.json")]
+fn openapi_document(document: &State<OpenApiDocument>) -> Json<&Value> {
+    Json(&document.0)
+}
+
+/// Routes of the API, mounted under [`API_BASE`] and, for app versions from before it, at `/`.
+fn api_routes() -> Vec<Route> {
+    routes![index, concerts_ics, concerts_csv, concerts_tsv, concerts_atom, concerts_rss, concerts_json_feed, past_concerts, concert_changes, concert, diagnostics]
+}
+
+/// The API serving whatever ends up in `store`, without anything refreshing it.
+fn app(store: SnapshotStore) -> Rocket<Build> {
+    rocket::build()
+        .manage(store)
+        .attach(AdHoc::config::<AppConfig>())
+        .mount(API_BASE, api_routes())
+        .mount(API_BASE, routes![openapi_document])
+        .mount("/", api_routes())
+        .attach(AdHoc::on_ignite("OpenAPI document", |rocket| async {
+            let document = get_openapi_from_rocket(&rocket);
+            rocket.manage(OpenApiDocument(document))
+        }))
+        .register("/", catchers![not_found, unprocessable_entity, internal_error])
+}
+
 #[launch]
 fn rocket() -> _ {
-    rocket::build().mount("/", routes![index])
+    app(SnapshotStore::new())
+        .attach(AdHoc::on_liftoff("Calendar refresh", |rocket| Box::pin(async move {
+            let store = rocket.state::<SnapshotStore>().unwrap().clone();
+            let config = rocket.state::<AppConfig>().unwrap();
+            let interval = config.refresh_interval;
+            let mut refresher = match CalendarRefresher::new(config.feeds.clone(), TimeZoneResolver::new(config.default_timezone), config.fetch) {
+                Ok(refresher) => refresher,
+                Err(e) => {
+                    // nothing will ever be fetched, say so instead of answering not_loaded forever
+                    log::error!("calendar refresh cannot start, {}", e);
+                    store.mark_failed(RefreshFailure::Fetch);
+                    return;
+                }
+            };
+            if let Some(data_dir) = &config.data_dir {
+                refresher = refresher.with_snapshot_dir(SnapshotDir::new(data_dir.clone()));
+            }
+
+            tokio::spawn(async move {
+                refresher.load_saved(&store).await;
+
+                let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+                loop {
+                    ticker.tick().await;
+                    refresher.refresh(&store).await;
+                }
+            });
+        })))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app;
+    use holo_wtf_api::calendar::{
+        feed::{Feed, FeedLocation},
+        snapshot::{CalendarRefresher, RefreshFailure, SnapshotStore},
+        source::MemorySource,
+        timezone::TimeZoneResolver
+    };
+    use chrono::{Datelike, Utc};
+    use chrono_tz::Asia::Tokyo;
+    use rocket::{
+        http::{ContentType, Header, Status},
+        local::asynchronous::Client,
+        serde::json::Value
+    };
+    use std::path::PathBuf;
+
+    const FIXTURE: &str = include_str!("../fixtures/concerts.ics");
+    /// Year the upcoming concerts of the fixture are dated in.
+    const FIXTURE_YEAR: &str = "2030";
+
+    /// The year the fixture's upcoming concerts are moved to, so they are still upcoming
+    /// whenever the tests run.
+    fn upcoming_year() -> String {
+        (Utc::now().year() + 1).to_string()
+    }
+
+    async fn fixture_store() -> SnapshotStore {
+        let feed = Feed { name: String::from("fixture"), location: FeedLocation::Path { path: PathBuf::from("fixtures/concerts.ics") }, default_platform: None };
+        let mut refresher = CalendarRefresher::with_sources(vec![(feed, Box::new(MemorySource::new(FIXTURE.replace(FIXTURE_YEAR, &upcoming_year()))))], TimeZoneResolver::new(Tokyo));
+        let store = SnapshotStore::new();
+        refresher.refresh(&store).await;
+        store
+    }
+
+    async fn client() -> Client {
+        Client::tracked(app(fixture_store().await)).await.unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn test_root_is_an_alias_of_v1() {
+        let client = client().await;
+        let v1 = client.get("/v1/concerts/past").dispatch().await.into_string().await.unwrap();
+        let root = client.get("/concerts/past").dispatch().await.into_string().await.unwrap();
+        assert_eq!(v1, root);
+
+        let response = client.get("/v1").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(client.get("/openapi.json").dispatch().await.status(), Status::NotFound);
+    }
+
+    /// The served schema against the one checked in, so that changing the shape of a response
+    /// shows up in review. Run with `UPDATE_SCHEMA=1` to write the new one after such a change.
+    #[rocket::async_test]
+    async fn test_openapi_matches_checked_in_schema() {
+        let client = client().await;
+        let response = client.get("/v1/openapi.json").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let schema: Value = response.into_json().await.unwrap();
+        let pretty = rocket::serde::json::to_pretty_string(&schema).unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write("schema/openapi.json", &pretty).unwrap();
+        }
+        assert_eq!(pretty, std::fs::read_to_string("schema/openapi.json").unwrap(), "schema/openapi.json is out of date");
+    }
+
+    #[rocket::async_test]
+    async fn test_index_lists_upcoming_concerts() {
+        let client = client().await;
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("X-Calendar-Stale"), Some("false"));
+        assert!(response.headers().get_one("X-Calendar-Fetched-At").is_some());
+
+        let body: Value = response.into_json().await.unwrap();
+        let titles: Vec<_> = body.as_array().unwrap().iter().map(|c| c["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, vec!["Fixture SPWN Live", "Fixture Z-aN Live"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_unchanged_list_is_not_modified() {
+        let client = client().await;
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=300"));
+        let last_modified = response.headers().get_one("Last-Modified").unwrap().to_string();
+        assert!(last_modified.ends_with(" GMT"));
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+        let response = client.get("/").header(Header::new("If-Modified-Since", last_modified.clone())).dispatch().await;
+        assert_eq!(response.status(), Status::NotModified);
+
+        let response = client.get("/")
+            .header(Header::new("If-Modified-Since", last_modified))
+            .header(Header::new("If-None-Match", "\"outdated\""))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/").header(Header::new("If-None-Match", etag.clone())).dispatch().await;
+        assert_eq!(response.status(), Status::NotModified);
+        assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+        assert!(response.into_string().await.is_none());
+
+        let filtered = client.get("/?platform=zan").header(Header::new("If-None-Match", etag.clone())).dispatch().await;
+        assert_eq!(filtered.status(), Status::Ok);
+        assert_ne!(filtered.headers().get_one("ETag"), Some(etag.as_str()));
+    }
+
+    #[rocket::async_test]
+    async fn test_concerts_ics_honours_filters() {
+        let client = client().await;
+        let response = client.get("/concerts.ics?platform=spwn").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::Calendar));
+
+        let ics = response.into_string().await.unwrap();
+        assert!(ics.contains("SUMMARY:Fixture SPWN Live\r\n"));
+        assert!(!ics.contains("Fixture Z-aN Live"));
+        assert!(!ics.contains("Fixture Past Live"));
+
+        let bad = client.get("/concerts.ics?format=vr").dispatch().await;
+        assert_eq!(bad.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn test_concerts_ics_reminders() {
+        let client = client().await;
+        let ics = client.get("/concerts.ics?remind=60,1440&platform=zan").dispatch().await.into_string().await.unwrap();
+        assert_eq!(ics.matches("BEGIN:VALARM").count(), 2);
+
+        let bad = client.get("/concerts.ics?remind=-5").dispatch().await;
+        assert_eq!(bad.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn test_spreadsheet_exports() {
+        let client = client().await;
+
+        let csv = client.get("/concerts.csv?platform=zan").dispatch().await;
+        assert_eq!(csv.content_type(), Some(ContentType::CSV));
+        let csv = csv.into_string().await.unwrap();
+        let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert!(lines[0].starts_with("\u{feff}id,title,status,platform,format,price_kind,price_yen,"));
+        let row = ",Fixture Z-aN Live,Upcoming,Zan,Both,Free,0,2030-02-01 10:00:00,2030-02-01 19:00:00,2030-02-01 12:00:00,2030-02-01 21:00:00,false,";
+        assert!(lines[1].contains(&row.replace(FIXTURE_YEAR, &upcoming_year())));
+        assert_eq!(lines.len(), 2);
+
+        let tsv = client.get("/concerts.tsv").dispatch().await;
+        assert_eq!(tsv.content_type().map(|c| c.sub().to_string()), Some(String::from("tab-separated-values")));
+        assert_eq!(tsv.into_string().await.unwrap().lines().count(), 3);
+    }
+
+    #[rocket::async_test]
+    async fn test_syndication_feeds() {
+        let client = client().await;
+
+        let atom = client.get("/concerts.atom").dispatch().await;
+        assert_eq!(atom.content_type(), Some(ContentType::new("application", "atom+xml")));
+        assert_eq!(atom.into_string().await.unwrap().matches("<entry>").count(), 2);
+
+        let rss = client.get("/concerts.rss?platform=spwn").dispatch().await;
+        assert_eq!(rss.content_type(), Some(ContentType::new("application", "rss+xml")));
+        assert_eq!(rss.into_string().await.unwrap().matches("<item>").count(), 1);
+
+        let response = client.get("/concerts.json").dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::new("application", "feed+json")));
+        let json: Value = response.into_json().await.unwrap();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["items"].as_array().unwrap().len(), 2);
+    }
+
+    #[rocket::async_test]
+    async fn test_index_rejects_bad_filter() {
+        let client = client().await;
+        let response = client.get("/?platform=twitch").dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn test_concert_by_id() {
+        let client = client().await;
+        let list: Value = client.get("/?platform=zan").dispatch().await.into_json().await.unwrap();
+        let id = list[0]["id"].as_str().unwrap();
+
+        let concert: Value = client.get(format!("/concerts/{}", id)).dispatch().await.into_json().await.unwrap();
+        assert_eq!(concert["title"], "Fixture Z-aN Live");
+
+        let missing = client.get("/concerts/00000000-0000-0000-0000-000000000000").dispatch().await;
+        assert_eq!(missing.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn test_past_concerts_and_diagnostics() {
+        let client = client().await;
+        let past: Value = client.get("/concerts/past").dispatch().await.into_json().await.unwrap();
+        assert_eq!(past["total"], 1);
+        assert_eq!(past["concerts"][0]["title"], "Fixture Past Live");
+
+        let diagnostics: Value = client.get("/diagnostics").dispatch().await.into_json().await.unwrap();
+        assert_eq!(diagnostics["rejected_count"], 1);
+        assert_eq!(diagnostics["rejected"][0]["uid"], "fixture-broken@holo-wtf-api");
+        assert_eq!(diagnostics["rejected"][0]["reason"]["kind"], "bad_summary");
+    }
+
+    #[rocket::async_test]
+    async fn test_concert_changes() {
+        let client = client().await;
+        let full: Value = client.get("/concerts/changes").dispatch().await.into_json().await.unwrap();
+        assert_eq!(full["full_sync"], true);
+        assert_eq!(full["added"].as_array().unwrap().len(), 3);
+
+        let cursor = full["cursor"].as_str().unwrap();
+        let delta: Value = client.get(format!("/concerts/changes?since={}", cursor)).dispatch().await.into_json().await.unwrap();
+        assert_eq!(delta["full_sync"], false);
+        assert_eq!(delta["cursor"], cursor);
+        assert!(delta["added"].as_array().unwrap().is_empty());
+
+        let bad = client.get("/concerts/changes?since=yesterday").dispatch().await;
+        assert_eq!(bad.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn test_not_loaded_yet() {
+        let client = Client::tracked(app(SnapshotStore::new())).await.unwrap();
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("10"));
+
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["code"], "not_loaded");
+        assert_eq!(body["retry_after"], 10);
+
+        for uri in ["/?max_price=cheap", "/concerts.ics?free=maybe", "/concerts.csv?from=tomorrow", "/concerts.json?platform=vhs"] {
+            assert_eq!(client.get(uri).dispatch().await.status(), Status::BadRequest, "{}", uri);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_upstream_failure_is_a_bad_gateway() {
+        let store = SnapshotStore::new();
+        store.mark_failed(RefreshFailure::Fetch);
+        let client = Client::tracked(app(store)).await.unwrap();
+
+        let response = client.get("/concerts/past").dispatch().await;
+        assert_eq!(response.status(), Status::BadGateway);
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["code"], "upstream_unavailable");
+        assert!(body["retry_after"].is_u64());
+    }
+
+    #[rocket::async_test]
+    async fn test_parse_failure_is_an_internal_error() {
+        let store = SnapshotStore::new();
+        store.mark_failed(RefreshFailure::Parse);
+        let client = Client::tracked(app(store)).await.unwrap();
+
+        let response = client.get("/diagnostics").dispatch().await;
+        assert_eq!(response.status(), Status::InternalServerError);
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["code"], "calendar_unparseable");
+    }
+
+    #[rocket::async_test]
+    async fn test_catchers_answer_with_json() {
+        let client = client().await;
+
+        let response = client.get("/nowhere").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["retry_after"], Value::Null);
+
+        let response = client.get("/concerts/not-a-uuid").dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["code"], "invalid_request");
+    }
+
+    /// Against a real server, only built with the `client` feature the client needs.
+    #[cfg(feature = "client")]
+    mod library_client {
+        use crate::{app, tests::fixture_store};
+        use holo_wtf_api::calendar::models::Platform;
+        use holo_wtf_api::client::{ClientError, ConcertListFilter, HoloWtfClient};
+        use rocket::fairing::AdHoc;
+        use url::Url;
+
+        /// The app listening on a free local port, for clients that need real HTTP.
+        async fn serve() -> Url {
+            let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            let figment = rocket::Config::figment()
+                .merge(("address", "127.0.0.1"))
+                .merge(("port", port))
+                .merge(("log_level", "off"))
+                .merge(("shutdown.ctrlc", false));
+
+            let (ready, started) = tokio::sync::oneshot::channel();
+            let ready = std::sync::Mutex::new(Some(ready));
+            let rocket = app(fixture_store().await)
+                .configure(figment)
+                .attach(AdHoc::on_liftoff("Ready", move |_| Box::pin(async move {
+                    if let Some(ready) = ready.lock().unwrap().take() {
+                        let _ = ready.send(());
+                    }
+                })));
+            tokio::spawn(rocket.launch());
+            started.await.unwrap();
+
+            Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap()
+        }
+
+        #[rocket::async_test]
+        async fn test_library_client() {
+            let client = HoloWtfClient::new(serve().await);
+
+            let spwn = ConcertListFilter { platforms: vec![Platform::Spwn], ..Default::default() };
+            let concerts = client.list_concerts(&spwn).await.unwrap();
+            assert_eq!(concerts.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), vec!["Fixture SPWN Live"]);
+
+            assert_eq!(client.get_concert(concerts[0].id).await.unwrap().uid, concerts[0].uid);
+            assert_eq!(client.list_past_concerts(1, 20).await.unwrap().total, 1);
+            assert!(client.get_changes(None).await.unwrap().full_sync);
+
+            match client.list_concerts(&ConcertListFilter { max_price: Some(-1), ..Default::default() }).await {
+                Err(ClientError::Api { status: 400, body }) => assert_eq!(body.code, "invalid_query"),
+                other => panic!("expected invalid_query, got {:?}", other)
+            }
+        }
+    }
 }
@@ -0,0 +1,276 @@
+use holo_wtf_api::calendar::{
+    models::{ConcertChanges, ConcertPage, Diagnostics, LiveConcert},
+    syndication::JsonFeed,
+};
+use crate::error::ErrorBody;
+use rocket::{
+    serde::json::{json, Value},
+    Phase,
+    Rocket
+};
+use schemars::{generate::SchemaSettings, JsonSchema, SchemaGenerator};
+use std::collections::BTreeMap;
+
+/// Where the versioned API is mounted, the same routes are also served at `/` for app
+/// versions that predate it.
+pub const API_BASE: &str = "/v1";
+
+#[derive(Debug, Clone, Copy)]
+pub enum ParamLocation {
+    Path,
+    Query
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub location: ParamLocation,
+    pub description: &'static str,
+    pub format: Option<&'static str>,
+}
+
+const fn query(name: &'static str, description: &'static str) -> Param {
+    Param { name, location: ParamLocation::Query, description, format: None }
+}
+
+/// Filters of [`ConcertQuery`](holo_wtf_api::calendar::filter::ConcertQuery).
+const FILTER_PARAMS: [Param; 6] = [
+    query("platform", "Comma separated platforms: niconico, spwn, tba, youtube, zan, zaiko or other."),
+    query("format", "Comma separated formats: online, irl or both. online and irl also match concerts held both ways."),
+    query("max_price", "Highest price in yen, compared against the cheapest tier. Never matches a TBD price."),
+    query("free", "true for free concerts only, false for paid ones only."),
+    query("from", "First day to include, as YYYY-MM-DD in the server's default timezone."),
+    query("to", "Last day to include, as YYYY-MM-DD in the server's default timezone."),
+];
+
+/// Parameters of [`ArchiveQuery`](holo_wtf_api::calendar::archive::ArchiveQuery).
+const ARCHIVE_PARAMS: [Param; 4] = [
+    query("page", "Page to return, starting at 1."),
+    query("per_page", "Concerts per page, 20 by default and at most 100."),
+    query("year", "Only concerts that started in this year."),
+    query("month", "Only concerts that started in this month, 1 to 12."),
+];
+
+const REMIND_PARAM: Param = query("remind", "Comma separated minutes before the start to add alarms at, e.g. 60,1440.");
+const SINCE_PARAM: Param = query("since", "Cursor of an earlier response, leave out for a full sync.");
+const ID_PARAM: Param = Param { name: "id", location: ParamLocation::Path, description: "ID of the concert.", format: Some("uuid") };
+
+enum Body {
+    Json(fn(&mut SchemaGenerator) -> Value),
+    /// JSON under a media type of its own.
+    JsonAs(&'static str, fn(&mut SchemaGenerator) -> Value),
+    Text(&'static str),
+}
+
+fn schema_of<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    generator.subschema_for::<T>().to_value()
+}
+
+/// What the document says about a route, which is not something Rocket knows about it.
+struct Operation {
+    summary: &'static str,
+    params: Vec<Param>,
+    body: Body,
+    /// Whether it is served as a snapshot document, with an `ETag` to revalidate against.
+    cached: bool,
+}
+
+/// The operation of a route by the name of its handler, `None` for a route that has not been
+/// documented yet.
+fn get_operation_from_route_name(name: &str) -> Option<Operation> {
+    let filtered = |summary, body| Operation { summary, params: FILTER_PARAMS.to_vec(), body, cached: true };
+
+    let operation = match name {
+        "index" => filtered("Upcoming and running concerts", Body::Json(schema_of::<Vec<LiveConcert>>)),
+        "concerts_ics" => Operation {
+            params: [&[REMIND_PARAM][..], &FILTER_PARAMS].concat(),
+            ..filtered("Upcoming concerts as an iCalendar feed", Body::Text("text/calendar"))
+        },
+        "concerts_csv" => filtered("Upcoming concerts as CSV, one row each", Body::Text("text/csv")),
+        "concerts_tsv" => filtered("Upcoming concerts as tab separated values, one row each", Body::Text("text/tab-separated-values")),
+        "concerts_atom" => filtered("Upcoming concerts as an Atom feed", Body::Text("application/atom+xml")),
+        "concerts_rss" => filtered("Upcoming concerts as an RSS 2.0 feed", Body::Text("application/rss+xml")),
+        "concerts_json_feed" => filtered("Upcoming concerts as a JSON Feed 1.1", Body::JsonAs("application/feed+json", schema_of::<JsonFeed>)),
+        "past_concerts" => Operation {
+            summary: "Concerts that have ended, newest first",
+            params: ARCHIVE_PARAMS.to_vec(),
+            body: Body::Json(schema_of::<ConcertPage>),
+            cached: true
+        },
+        "concert_changes" => Operation {
+            summary: "Concerts added, updated or removed since a cursor",
+            params: vec![SINCE_PARAM],
+            body: Body::Json(schema_of::<ConcertChanges>),
+            cached: true
+        },
+        "concert" => Operation {
+            summary: "A single concert, past ones included",
+            params: vec![ID_PARAM],
+            body: Body::Json(schema_of::<LiveConcert>),
+            cached: true
+        },
+        "diagnostics" => Operation {
+            summary: "Parse results of the last refresh",
+            params: vec![],
+            body: Body::Json(schema_of::<Diagnostics>),
+            cached: true
+        },
+        "openapi_document" => Operation {
+            summary: "This document",
+            params: vec![],
+            body: Body::Json(|_| json!({ "type": "object" })),
+            cached: false
+        },
+        _ => return None
+    };
+    Some(operation)
+}
+
+/// `/v1/concerts/<id>` the way OpenAPI writes it, `/v1/concerts/{id}`.
+fn get_openapi_path_from_route(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Some(name) => format!("{{{}}}", name.trim_end_matches("..")),
+            None => segment.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn get_value_from_param(param: &Param) -> Value {
+    let mut schema = json!({ "type": "string" });
+    if let Some(format) = param.format {
+        schema["format"] = json!(format);
+    }
+
+    match param.location {
+        ParamLocation::Path => json!({ "name": param.name, "in": "path", "required": true, "description": param.description, "schema": schema }),
+        ParamLocation::Query => json!({ "name": param.name, "in": "query", "required": false, "description": param.description, "schema": schema }),
+    }
+}
+
+/// Headers of every response built from a snapshot. The bodies keep the shape app versions
+/// from before `/v1` decode, so these headers are the only place saying how old the data is.
+fn get_snapshot_headers() -> Value {
+    json!({
+        "X-Calendar-Stale": {
+            "description": "true while the data was loaded from disk after a restart and upstream has not been fetched since. Clients should show it as possibly outdated.",
+            "required": true,
+            "schema": { "type": "string", "enum": ["true", "false"] }
+        },
+        "X-Calendar-Fetched-At": {
+            "description": "When upstream was last fetched, RFC 3339.",
+            "required": true,
+            "schema": { "type": "string", "format": "date-time" }
+        },
+        "ETag": {
+            "description": "Tag of the body, send it back in If-None-Match to get a 304 while nothing changed.",
+            "required": true,
+            "schema": { "type": "string" }
+        },
+        "Last-Modified": {
+            "description": "When the concerts last changed, or started or ended. Send it back in If-Modified-Since to get a 304 while nothing changed, If-None-Match wins when both are sent.",
+            "required": true,
+            "schema": { "type": "string" }
+        }
+    })
+}
+
+fn get_value_from_operation(operation: &Operation, generator: &mut SchemaGenerator) -> Value {
+    let content = match &operation.body {
+        Body::Json(schema) => json!({ "application/json": { "schema": schema(generator) } }),
+        Body::JsonAs(content_type, schema) => json!({ content_type.to_string(): { "schema": schema(generator) } }),
+        Body::Text(content_type) => json!({ content_type.to_string(): { "schema": { "type": "string" } } }),
+    };
+
+    let mut ok = json!({ "description": "OK", "content": content });
+    if operation.cached {
+        ok["headers"] = get_snapshot_headers();
+    }
+
+    let mut responses = BTreeMap::new();
+    responses.insert("200", ok);
+    if operation.cached {
+        responses.insert("304", json!({ "description": "Not modified since the ETag sent in If-None-Match, or the date sent in If-Modified-Since" }));
+    }
+    responses.insert("default", json!({
+        "description": "Error",
+        "content": { "application/json": { "schema": generator.subschema_for::<ErrorBody>().to_value() } }
+    }));
+
+    json!({
+        "summary": operation.summary,
+        "parameters": operation.params.iter().map(get_value_from_param).collect::<Vec<_>>(),
+        "responses": responses
+    })
+}
+
+/// The document built from the mounted routes when the server ignites.
+pub struct OpenApiDocument(pub Value);
+
+/// OpenAPI 3 document of every route mounted under [`API_BASE`], with the schemas taken from
+/// the types the routes answer with. Routes missing from [`get_operation_from_route_name`] are
+/// left out, the tests make sure there are none.
+pub fn get_openapi_from_rocket<P: Phase>(rocket: &Rocket<P>) -> Value {
+    let mut generator = SchemaGenerator::new(SchemaSettings::openapi3());
+    let mut paths: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
+
+    for route in rocket.routes() {
+        let path = route.uri.path();
+        if path != API_BASE && !path.starts_with(&format!("{}/", API_BASE)) {
+            continue;
+        }
+        let Some(operation) = route.name.as_deref().and_then(get_operation_from_route_name) else {
+            continue;
+        };
+
+        paths
+            .entry(get_openapi_path_from_route(path))
+            .or_default()
+            .insert(route.method.as_str().to_lowercase(), get_value_from_operation(&operation, &mut generator));
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "holo.wtf API",
+            "description": "API for the HoloCal app. Whether the data is stale is told by the X-Calendar-Stale header of each response, not by the bodies.",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": { "schemas": generator.take_definitions(true) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::openapi::{get_openapi_path_from_route, get_operation_from_route_name, ParamLocation, API_BASE};
+
+    #[test]
+    fn test_openapi_path() {
+        assert_eq!(get_openapi_path_from_route("/v1/concerts/<id>"), "/v1/concerts/{id}");
+        assert_eq!(get_openapi_path_from_route("/v1/concerts.ics"), "/v1/concerts.ics");
+    }
+
+    #[test]
+    fn test_every_route_is_documented() {
+        for route in crate::app(holo_wtf_api::calendar::snapshot::SnapshotStore::new()).routes() {
+            if !route.uri.path().starts_with(API_BASE) {
+                continue;
+            }
+
+            let name = route.name.as_deref().unwrap_or_default();
+            let operation = get_operation_from_route_name(name);
+            assert!(operation.is_some(), "{} is missing from the OpenAPI document", route.uri);
+
+            let path_params: Vec<&str> = operation.unwrap().params.iter()
+                .filter(|p| matches!(p.location, ParamLocation::Path))
+                .map(|p| p.name)
+                .collect();
+            let segments: Vec<&str> = route.uri.path().split('/')
+                .filter_map(|s| s.strip_prefix('<').and_then(|s| s.strip_suffix('>')))
+                .collect();
+            assert_eq!(path_params, segments, "path parameters of {}", route.uri);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use holo_wtf_api::calendar::snapshot::CalendarSnapshot;
+use crate::config::AppConfig;
+use chrono::{DateTime, Utc};
+use rocket::{
+    http::{ContentType, Status},
+    request::Request,
+    response::{self, Responder, Response},
+    serde::{json, Serialize}
+};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+
+/// A document built from a calendar snapshot, with headers telling clients how old the data
+/// is and how long they may keep it.
+///
+/// `X-Calendar-Fetched-At` is when upstream was last fetched, `X-Calendar-Stale` is `true`
+/// while the data still comes from what was saved on disk before a restart. The `ETag` is
+/// taken from the body itself, since filters and concert statuses change it between two
+/// snapshots, and a matching `If-None-Match` gets an empty 304. `Last-Modified` is
+/// [`CalendarSnapshot::last_modified`], so polling upstream without finding anything new does
+/// not move it, and is checked against `If-Modified-Since` when no `If-None-Match` was sent.
+pub struct SnapshotDocument {
+    body: String,
+    content_type: ContentType,
+    fetched_at: DateTime<Utc>,
+    last_modified: DateTime<Utc>,
+    stale: bool,
+}
+
+impl SnapshotDocument {
+    pub fn new(body: String, content_type: ContentType, snapshot: &CalendarSnapshot) -> Self {
+        Self {
+            body,
+            content_type,
+            fetched_at: snapshot.fetched_at,
+            last_modified: snapshot.last_modified(Utc::now()),
+            stale: snapshot.stale
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for SnapshotDocument {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let etag = get_etag_from_body(&self.body);
+        let max_age = request.rocket().state::<AppConfig>().map_or(0, |c| c.refresh_interval);
+
+        let mut response = Response::build();
+        response
+            .raw_header("ETag", etag.clone())
+            .raw_header("Last-Modified", self.last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+            .raw_header("Cache-Control", format!("public, max-age={}", max_age))
+            .raw_header("X-Calendar-Fetched-At", self.fetched_at.to_rfc3339())
+            .raw_header("X-Calendar-Stale", self.stale.to_string());
+
+        // If-Modified-Since is only looked at without If-None-Match, as RFC 9110 asks for
+        let not_modified = match request.headers().get_one("If-None-Match") {
+            Some(tags) => etag_matches(tags, &etag),
+            None => request.headers().get_one("If-Modified-Since").is_some_and(|date| not_modified_since(date, self.last_modified))
+        };
+        if not_modified {
+            return response.status(Status::NotModified).ok();
+        }
+
+        response
+            .header(self.content_type)
+            .sized_body(self.body.len(), Cursor::new(self.body))
+            .ok()
+    }
+}
+
+/// JSON built from a calendar snapshot, served as a [`SnapshotDocument`].
+pub struct SnapshotJson<T> {
+    body: T,
+    content_type: ContentType,
+    fetched_at: DateTime<Utc>,
+    last_modified: DateTime<Utc>,
+    stale: bool,
+}
+
+impl<T> SnapshotJson<T> {
+    pub fn new(body: T, snapshot: &CalendarSnapshot) -> Self {
+        Self {
+            body,
+            content_type: ContentType::JSON,
+            fetched_at: snapshot.fetched_at,
+            last_modified: snapshot.last_modified(Utc::now()),
+            stale: snapshot.stale
+        }
+    }
+
+    /// For JSON formats with a media type of their own, e.g. `application/feed+json`.
+    pub fn with_content_type(self, content_type: ContentType) -> Self {
+        Self { content_type, ..self }
+    }
+
+    /// For a body that changes with every fetch rather than with the concerts.
+    pub fn modified_at(self, last_modified: DateTime<Utc>) -> Self {
+        Self { last_modified, ..self }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for SnapshotJson<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = json::to_string(&self.body).map_err(|_| Status::InternalServerError)?;
+        SnapshotDocument { body, content_type: self.content_type, fetched_at: self.fetched_at, last_modified: self.last_modified, stale: self.stale }
+            .respond_to(request)
+    }
+}
+
+pub fn get_etag_from_body(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Weak comparison of an `If-None-Match` list against our tag, as RFC 9110 asks for.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Whether nothing changed since the HTTP date of `If-Modified-Since`, a date that cannot be
+/// parsed is ignored.
+fn not_modified_since(if_modified_since: &str, last_modified: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc2822(if_modified_since).is_ok_and(|since| last_modified.timestamp() <= since.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::response::{etag_matches, get_etag_from_body, not_modified_since};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_etag_follows_body() {
+        assert_eq!(get_etag_from_body("[]"), get_etag_from_body("[]"));
+        assert_ne!(get_etag_from_body("[]"), get_etag_from_body("[{}]"));
+        assert!(get_etag_from_body("[]").starts_with('"'));
+    }
+
+    #[test]
+    fn test_etag_matching() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"old\", W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"old\"", "\"abc\""));
+    }
+
+    #[test]
+    fn test_modified_since() {
+        let last_modified = Utc.with_ymd_and_hms(2029, 1, 1, 12, 0, 0).unwrap() + chrono::Duration::milliseconds(500);
+        assert!(not_modified_since("Mon, 01 Jan 2029 12:00:00 GMT", last_modified));
+        assert!(not_modified_since("Tue, 02 Jan 2029 00:00:00 GMT", last_modified));
+        assert!(!not_modified_since("Mon, 01 Jan 2029 11:59:59 GMT", last_modified));
+        assert!(!not_modified_since("yesterday", last_modified));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::calendar::models::{ConcertChanges, ConcertPage, LiveConcert};
+use schemars::{generate::SchemaSettings, SchemaGenerator};
+use serde_json::Value;
+use std::fmt::Write;
+
+const HEADER: &str = "\
+// Swift mirrors of the holo-wtf-api models, generated from the Rust types. Do not edit, run
+// `cargo run --bin gen_swift` in holo-wtf-api instead.
+//
+// Dates are RFC 3339, decode them with `JSONDecoder.DateDecodingStrategy.iso8601`. Whether the
+// data is stale is not part of these bodies, read the `X-Calendar-Stale` response header.
+
+import Foundation
+";
+
+/// Swift `Codable` types for everything the app decodes, generated from the same schemas as
+/// the OpenAPI document so they follow the serde representation of the models. This is what
+/// `gen_swift` writes to `schema/Models.swift`.
+pub fn get_swift_from_models() -> String {
+    let mut generator = SchemaGenerator::new(SchemaSettings::openapi3());
+    generator.subschema_for::<LiveConcert>();
+    generator.subschema_for::<ConcertPage>();
+    generator.subschema_for::<ConcertChanges>();
+
+    let mut swift = String::from(HEADER);
+    for (name, schema) in generator.take_definitions(true).iter() {
+        swift.push('\n');
+        swift.push_str(&get_swift_from_schema(name, schema));
+    }
+    swift
+}
+
+/// `is_all_day` as a Swift property name, `isAllDay`.
+fn get_property_name(field: &str) -> String {
+    let mut name = String::with_capacity(field.len());
+    let mut upper = false;
+    for c in field.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            name.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// `MultiTier` as a Swift enum case, `multiTier`.
+fn get_case_name(variant: &str) -> String {
+    let mut chars = variant.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+fn get_swift_type(schema: &Value) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return reference.rsplit('/').next().unwrap_or(reference).to_string();
+    }
+
+    match (schema["type"].as_str(), schema["format"].as_str()) {
+        (Some("string"), Some("date-time")) => String::from("Date"),
+        (Some("string"), Some("uri")) => String::from("URL"),
+        (Some("string"), Some("uuid")) => String::from("UUID"),
+        (Some("string"), _) => String::from("String"),
+        (Some("integer"), _) => String::from("Int"),
+        (Some("number"), _) => String::from("Double"),
+        (Some("boolean"), _) => String::from("Bool"),
+        (Some("array"), _) => format!("[{}]", get_swift_type(&schema["items"])),
+        _ => panic!("no Swift type for the schema {}", schema)
+    }
+}
+
+fn write_doc_comment(swift: &mut String, schema: &Value, indent: &str) {
+    if let Some(description) = schema["description"].as_str() {
+        for line in description.lines() {
+            let _ = writeln!(swift, "{}/// {}", indent, line);
+        }
+    }
+}
+
+fn write_coding_key(swift: &mut String, field: &str) {
+    let property = get_property_name(field);
+    if property == field {
+        let _ = writeln!(swift, "        case {}", property);
+    } else {
+        let _ = writeln!(swift, "        case {} = \"{}\"", property, field);
+    }
+}
+
+/// The name of the property every variant of a tagged `oneOf` fixes to a single value, and
+/// the name of the other one carrying the content, if any.
+fn get_tag_and_content_keys(variants: &[Value]) -> Option<(String, Option<String>)> {
+    let first = variants.first()?["properties"].as_object()?;
+    let tag = first.iter().find(|(_, p)| p["enum"].as_array().is_some_and(|e| e.len() == 1))?.0.clone();
+    let content = variants
+        .iter()
+        .filter_map(|v| v["properties"].as_object())
+        .flat_map(|p| p.keys())
+        .find(|key| **key != tag)
+        .cloned();
+    Some((tag, content))
+}
+
+fn get_swift_from_schema(name: &str, schema: &Value) -> String {
+    if let Some(cases) = schema["enum"].as_array() {
+        get_swift_from_string_enum(name, schema, cases)
+    } else if let Some(variants) = schema["oneOf"].as_array() {
+        get_swift_from_tagged_enum(name, schema, variants)
+    } else if schema["type"] == "object" {
+        get_swift_from_struct(name, schema)
+    } else {
+        panic!("no Swift type for {}: {}", name, schema)
+    }
+}
+
+fn get_swift_from_struct(name: &str, schema: &Value) -> String {
+    let empty = Default::default();
+    let properties = schema["properties"].as_object().unwrap_or(&empty);
+    let required: Vec<&str> = schema["required"].as_array().map_or(vec![], |r| r.iter().filter_map(Value::as_str).collect());
+
+    let mut swift = String::new();
+    write_doc_comment(&mut swift, schema, "");
+    let _ = writeln!(swift, "public struct {}: Codable, Hashable {{", name);
+    for (field, property) in properties {
+        let optional = !required.contains(&field.as_str()) || property["nullable"] == true;
+        write_doc_comment(&mut swift, property, "    ");
+        let _ = writeln!(swift, "    public let {}: {}{}", get_property_name(field), get_swift_type(property), if optional { "?" } else { "" });
+    }
+
+    swift.push_str("\n    enum CodingKeys: String, CodingKey {\n");
+    for field in properties.keys() {
+        write_coding_key(&mut swift, field);
+    }
+    swift.push_str("    }\n}\n");
+    swift
+}
+
+fn get_swift_from_string_enum(name: &str, schema: &Value, cases: &[Value]) -> String {
+    let mut swift = String::new();
+    write_doc_comment(&mut swift, schema, "");
+    let _ = writeln!(swift, "public enum {}: String, Codable, CaseIterable {{", name);
+    for case in cases.iter().filter_map(Value::as_str) {
+        let _ = writeln!(swift, "    case {} = \"{}\"", get_case_name(case), case);
+    }
+    swift.push_str("}\n");
+    swift
+}
+
+/// An enum with associated values for a serde enum tagged by one property, with its content in
+/// another, e.g. `{"tag": "Fixed", "content": 3000}`.
+fn get_swift_from_tagged_enum(name: &str, schema: &Value, variants: &[Value]) -> String {
+    let (tag_key, content_key) = get_tag_and_content_keys(variants)
+        .unwrap_or_else(|| panic!("{} is not a tagged enum: {}", name, schema));
+
+    // (serde name, Swift case, Swift type of the content)
+    let cases: Vec<(String, String, Option<String>)> = variants
+        .iter()
+        .map(|variant| {
+            let tag = variant["properties"][&tag_key]["enum"][0].as_str()
+                .unwrap_or_else(|| panic!("a variant of {} has no tag: {}", name, variant));
+            let content = content_key.as_ref()
+                .map(|key| &variant["properties"][key])
+                .filter(|content| !content.is_null())
+                .map(get_swift_type);
+            (tag.to_string(), get_case_name(tag), content)
+        })
+        .collect();
+
+    let mut swift = String::new();
+    write_doc_comment(&mut swift, schema, "");
+    let _ = writeln!(swift, "public enum {}: Codable, Hashable {{", name);
+    for (_, case, content) in cases.iter() {
+        match content {
+            Some(content) => { let _ = writeln!(swift, "    case {}({})", case, content); },
+            None => { let _ = writeln!(swift, "    case {}", case); },
+        }
+    }
+
+    swift.push_str("\n    enum CodingKeys: String, CodingKey {\n");
+    write_coding_key(&mut swift, &tag_key);
+    if let Some(content_key) = &content_key {
+        write_coding_key(&mut swift, content_key);
+    }
+    swift.push_str("    }\n");
+
+    let tag = get_property_name(&tag_key);
+    let content_property = content_key.as_deref().map(get_property_name).unwrap_or_default();
+
+    swift.push_str("\n    public init(from decoder: Decoder) throws {\n");
+    swift.push_str("        let container = try decoder.container(keyedBy: CodingKeys.self)\n");
+    let _ = writeln!(swift, "        switch try container.decode(String.self, forKey: .{}) {{", tag);
+    for (serde_name, case, content) in cases.iter() {
+        match content {
+            Some(content) => { let _ = writeln!(swift, "        case \"{}\": self = .{}(try container.decode({}.self, forKey: .{}))", serde_name, case, content, content_property); },
+            None => { let _ = writeln!(swift, "        case \"{}\": self = .{}", serde_name, case); },
+        }
+    }
+    let _ = writeln!(swift, "        case let unknown: throw DecodingError.dataCorruptedError(forKey: .{}, in: container, debugDescription: \"unknown {} \\(unknown)\")", tag, name);
+    swift.push_str("        }\n    }\n");
+
+    swift.push_str("\n    public func encode(to encoder: Encoder) throws {\n");
+    swift.push_str("        var container = encoder.container(keyedBy: CodingKeys.self)\n");
+    swift.push_str("        switch self {\n");
+    for (serde_name, case, content) in cases.iter() {
+        match content {
+            Some(_) => {
+                let _ = writeln!(swift, "        case .{}(let content):", case);
+                let _ = writeln!(swift, "            try container.encode(\"{}\", forKey: .{})", serde_name, tag);
+                let _ = writeln!(swift, "            try container.encode(content, forKey: .{})", content_property);
+            },
+            None => { let _ = writeln!(swift, "        case .{}: try container.encode(\"{}\", forKey: .{})", case, serde_name, tag); },
+        }
+    }
+    swift.push_str("        }\n    }\n}\n");
+    swift
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::swift::{get_case_name, get_property_name, get_swift_from_models};
+
+    #[test]
+    fn test_names() {
+        assert_eq!(get_property_name("is_all_day"), "isAllDay");
+        assert_eq!(get_property_name("title"), "title");
+        assert_eq!(get_case_name("MultiTier"), "multiTier");
+    }
+
+    /// The generated models against the ones checked in for the app, so a change to the serde
+    /// representation shows up in review. Run with `UPDATE_SCHEMA=1`, or run `gen_swift`, to write
+    /// the new ones.
+    #[test]
+    fn test_swift_matches_checked_in_models() {
+        let swift = get_swift_from_models();
+
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write("schema/Models.swift", &swift).unwrap();
+        }
+        assert_eq!(swift, std::fs::read_to_string("schema/Models.swift").unwrap(), "schema/Models.swift is out of date");
+    }
+}